@@ -0,0 +1,154 @@
+//! Several calls sent to the daemon as one json-rpc batch.
+
+use serde_json::Value;
+
+use crate::btc::BtcAddress;
+use crate::error::Result;
+use crate::{
+    result_from_envelope, Electrum, ElectrumMethod, JsonRpcBody, JsonRpcBodyBuilder, Param,
+};
+
+/// Calls accumulated for one batch, created with `Electrum::batch`.
+///
+/// # Examples
+/// ```no_run
+/// # use electrum_jsonrpc::{btc::BtcAddress, Electrum};
+/// # async fn run(client: Electrum) -> electrum_jsonrpc::error::Result<()> {
+/// let results = client
+///     .batch()
+///     .get_info()
+///     .get_address_balance(&BtcAddress::new("tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn"))
+///     .send()
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct BatchBuilder<'a> {
+    client: &'a Electrum,
+    bodies: Vec<JsonRpcBodyBuilder>,
+}
+
+impl<'a> BatchBuilder<'a> {
+    pub(crate) fn new(client: &'a Electrum) -> Self {
+        Self {
+            client,
+            bodies: Vec::new(),
+        }
+    }
+
+    fn push(mut self, body: JsonRpcBodyBuilder) -> Self {
+        self.bodies.push(body);
+        self
+    }
+
+    /// Add `getinfo`.
+    pub fn get_info(self) -> Self {
+        self.push(JsonRpcBody::new().method(ElectrumMethod::GetInfo))
+    }
+
+    /// Add `getbalance` of the wallet.
+    pub fn get_balance(self) -> Self {
+        self.push(JsonRpcBody::new().method(ElectrumMethod::GetBalance))
+    }
+
+    /// Add `getaddressbalance` of `address`.
+    pub fn get_address_balance(self, address: &BtcAddress) -> Self {
+        self.push(
+            JsonRpcBody::new()
+                .method(ElectrumMethod::GetAddressBalance)
+                .add_param(Param::BtcAddress, Value::from(address)),
+        )
+    }
+
+    /// Add `get_tx_status` of transaction `txid`.
+    pub fn get_tx_status(self, txid: &str) -> Self {
+        self.push(
+            JsonRpcBody::new()
+                .method(ElectrumMethod::GetTxStatus)
+                .add_param(Param::Txid, Value::from(txid)),
+        )
+    }
+
+    /// Number of calls added so far.
+    pub fn len(&self) -> usize {
+        self.bodies.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bodies.is_empty()
+    }
+
+    /// Send the calls, split per `with_max_batch`, and return their results
+    /// in the order they were added. Responses are matched to the calls by id.
+    /// Fails if any call fails.
+    pub async fn send(self) -> Result<Vec<Value>> {
+        if self.bodies.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let strict = self.client.strict_parsing;
+        self.client
+            .call_many(self.bodies)
+            .await?
+            .into_iter()
+            .map(|json| result_from_envelope(json, strict))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::ext::tests::*;
+
+    #[tokio::test]
+    async fn batch_matched_by_id() {
+        let daemon = MockDaemon::start(|_, req| {
+            let replies: Vec<Value> = req
+                .as_array()
+                .unwrap()
+                .iter()
+                .rev()
+                .map(|call| {
+                    let height = match call["method"].as_str() {
+                        Some("getinfo") => json!(1_937_000 + call["id"].as_u64().unwrap()),
+                        _ => Value::Null,
+                    };
+                    json!({"jsonrpc": "2.0", "result": {"blockchain_height": height}, "id": call["id"]})
+                })
+                .collect();
+            MockReply::raw(&Value::from(replies).to_string())
+        });
+        let electrum = daemon.electrum();
+
+        let batch = electrum.batch().get_info().get_info();
+        assert_eq!(batch.len(), 2);
+        let results = batch.send().await.unwrap();
+
+        assert_eq!(results[0]["blockchain_height"], 1_937_000);
+        assert_eq!(results[1]["blockchain_height"], 1_937_001);
+
+        let requests = daemon.requests();
+        assert_eq!(requests.len(), 1);
+        let ids: Vec<&Value> = requests[0]
+            .body
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|call| &call["id"])
+            .collect();
+        assert_eq!(ids, vec![&json!(0), &json!(1)]);
+    }
+
+    #[tokio::test]
+    async fn empty_batch_not_sent() {
+        let daemon = MockDaemon::start(|_, _| MockReply::result(json!(null)));
+
+        let results = daemon.electrum().batch().send().await.unwrap();
+
+        assert!(results.is_empty());
+        assert_eq!(daemon.calls(), 0);
+    }
+}
@@ -1,6 +1,15 @@
-use rust_decimal::prelude::FromStr;
+use std::str::FromStr;
+
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+use crate::error::{ElectrumRpcError, Result};
+
+/// Number of decimal places in one satoshi.
+const BTC_DECIMAL_PLACES: u32 = 8;
 
 /// Represents btc address
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -9,12 +18,271 @@ pub struct BtcAddress<'a> {
     pub address: &'a str,
 }
 
-// todo: address verification
 impl<'a> BtcAddress<'a> {
-    /// Create a new address from String
+    /// Create a new address from String without any checks,
+    /// for trusted input such as addresses returned by the daemon.
     pub fn new(address: &'a str) -> Self {
         Self { address }
     }
+
+    /// Create a new address, checking it is a plausible bitcoin address:
+    /// base58check P2PKH/P2SH or a bech32/bech32m segwit address, with a valid
+    /// checksum and, for segwit, a witness program of a valid length.
+    pub fn parse(address: &'a str) -> Result<Self> {
+        address_kind(address)?;
+        Ok(Self { address })
+    }
+
+    /// Like `parse`, also checking the address belongs to `network`:
+    /// `bc1`/`1`/`3` on mainnet, `tb1`/`m`/`n`/`2` on testnet and `bcrt1`/`m`/`n`/`2` on regtest.
+    /// Fails with `ElectrumRpcError::NetworkMismatch` otherwise.
+    pub fn parse_for_network(address: &'a str, network: Network) -> Result<Self> {
+        let matches = match address_kind(address)? {
+            AddressKind::Base58(version) => network.base58_versions().contains(&version),
+            AddressKind::Segwit(hrp) => hrp == network.segwit_hrp(),
+        };
+
+        if !matches {
+            return Err(ElectrumRpcError::NetworkMismatch {
+                address: address.to_string(),
+                network,
+            });
+        }
+
+        Ok(Self { address })
+    }
+}
+
+/// Bitcoin network the daemon runs on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Network {
+    Mainnet,
+    Testnet,
+    Regtest,
+}
+
+impl Network {
+    /// Version bytes of P2PKH and P2SH addresses.
+    fn base58_versions(&self) -> [u8; 2] {
+        match self {
+            Self::Mainnet => [0x00, 0x05],
+            Self::Testnet | Self::Regtest => [0x6f, 0xc4],
+        }
+    }
+
+    /// Human readable part of segwit addresses.
+    fn segwit_hrp(&self) -> &'static str {
+        match self {
+            Self::Mainnet => "bc",
+            Self::Testnet => "tb",
+            Self::Regtest => "bcrt",
+        }
+    }
+}
+
+impl std::fmt::Display for Network {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Mainnet => f.write_str("mainnet"),
+            Self::Testnet => f.write_str("testnet"),
+            Self::Regtest => f.write_str("regtest"),
+        }
+    }
+}
+
+/// Type of the output script an address pays to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AddressType {
+    P2pkh,
+    P2sh,
+    P2wpkh,
+    P2wsh,
+    P2tr,
+}
+
+impl AddressType {
+    /// Type of `address`, failing with `ElectrumRpcError::InvalidAddress`
+    /// if it isn't a valid address of a known type.
+    pub fn of(address: &str) -> Result<Self> {
+        let invalid = || ElectrumRpcError::InvalidAddress(address.to_string());
+
+        match address_kind(address)? {
+            AddressKind::Base58(0x00) | AddressKind::Base58(0x6f) => Ok(Self::P2pkh),
+            AddressKind::Base58(_) => Ok(Self::P2sh),
+            AddressKind::Segwit(_) => {
+                let address = address.to_ascii_lowercase();
+                let (_, data) = address.rsplit_once('1').ok_or_else(invalid)?;
+                // witness version and checksum aside, 5 bits per character
+                let program_len = (data.len() - 7) * 5 / 8;
+
+                match (data.as_bytes()[0], program_len) {
+                    (b'q', 20) => Ok(Self::P2wpkh),
+                    (b'q', 32) => Ok(Self::P2wsh),
+                    (b'p', 32) => Ok(Self::P2tr),
+                    _ => Err(invalid()),
+                }
+            }
+        }
+    }
+
+    /// Smallest output value in satoshis relayed by nodes at the default
+    /// dust relay fee of 3 sat/vB.
+    pub fn dust_threshold(&self) -> u64 {
+        match self {
+            Self::P2pkh => 546,
+            Self::P2sh => 540,
+            Self::P2wpkh => 294,
+            Self::P2wsh | Self::P2tr => 330,
+        }
+    }
+}
+
+/// Whether an output of `amount` BTC to `script_type` is below the dust
+/// threshold, so nodes won't relay a transaction creating it.
+pub fn is_dust(amount: Decimal, script_type: AddressType) -> bool {
+    amount * Decimal::new(100_000_000, 0) < Decimal::from(script_type.dust_threshold())
+}
+
+/// Encoding of a plausible address, with what tells its network apart.
+enum AddressKind {
+    /// Version byte.
+    Base58(u8),
+    /// Lowercase human readable part.
+    Segwit(String),
+}
+
+fn address_kind(address: &str) -> Result<AddressKind> {
+    segwit_hrp(address)
+        .map(AddressKind::Segwit)
+        .or_else(|| base58_version(address).map(AddressKind::Base58))
+        .ok_or_else(|| ElectrumRpcError::InvalidAddress(address.to_string()))
+}
+
+/// Characters of base58 encoding, in the order of their values.
+const BASE58_ALPHABET: &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// Version bytes of mainnet and testnet P2PKH and P2SH addresses.
+const BASE58_VERSIONS: [u8; 4] = [0x00, 0x05, 0x6f, 0xc4];
+
+/// Characters of bech32 encoding, in the order of their values.
+const BECH32_CHARSET: &[u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+/// Human readable parts of mainnet, testnet and regtest segwit addresses.
+const SEGWIT_HRPS: [&str; 3] = ["bc", "tb", "bcrt"];
+
+/// Version byte of `address` if it decodes to a known version byte,
+/// a 20 byte hash and a 4 byte checksum matching the first 21 bytes.
+fn base58_version(address: &str) -> Option<u8> {
+    let mut bytes = [0u8; 25];
+    for c in address.bytes() {
+        let mut carry = match BASE58_ALPHABET.iter().position(|&a| a == c) {
+            Some(value) => value as u32,
+            None => return None,
+        };
+        for byte in bytes.iter_mut().rev() {
+            carry += *byte as u32 * 58;
+            *byte = carry as u8;
+            carry >>= 8;
+        }
+        if carry != 0 {
+            return None;
+        }
+    }
+
+    // Each leading zero byte is encoded as a leading `1`.
+    let ones = address.bytes().take_while(|&c| c == b'1').count();
+    let zeros = bytes.iter().take_while(|&&byte| byte == 0).count();
+
+    let checksum = Sha256::digest(Sha256::digest(&bytes[..21]));
+
+    Some(bytes[0]).filter(|version| {
+        !address.is_empty()
+            && ones == zeros
+            && BASE58_VERSIONS.contains(version)
+            && checksum[..4] == bytes[21..]
+    })
+}
+
+/// Human readable part of `address` if it is a segwit address with
+/// a valid bech32 (v0) or bech32m (v1+) checksum and a witness program
+/// of 2 to 40 bytes, 20 or 32 for v0.
+fn segwit_hrp(address: &str) -> Option<String> {
+    if address.len() > 90
+        || (address.bytes().any(|c| c.is_ascii_lowercase())
+            && address.bytes().any(|c| c.is_ascii_uppercase()))
+    {
+        return None;
+    }
+
+    let address = address.to_ascii_lowercase();
+    let (hrp, data) = address.rsplit_once('1')?;
+    if !SEGWIT_HRPS.contains(&hrp) || data.len() < 7 {
+        return None;
+    }
+
+    let values: Vec<u8> = data
+        .bytes()
+        .map(|c| {
+            BECH32_CHARSET
+                .iter()
+                .position(|&b| b == c)
+                .map(|value| value as u8)
+        })
+        .collect::<Option<_>>()?;
+
+    let mut checked: Vec<u8> = hrp.bytes().map(|c| c >> 5).collect();
+    checked.push(0);
+    checked.extend(hrp.bytes().map(|c| c & 31));
+    checked.extend(&values);
+
+    let valid = matches!(
+        (values[0], bech32_polymod(&checked)),
+        (0, 1) | (1..=16, 0x2bc8_30a3)
+    );
+    let program = witness_program(&values[1..values.len() - 6])?;
+    let length_valid = match values[0] {
+        0 => program.len() == 20 || program.len() == 32,
+        _ => (2..=40).contains(&program.len()),
+    };
+
+    Some(hrp.to_string()).filter(|_| valid && length_valid)
+}
+
+/// Bytes of the witness program encoded by the 5 bit `values`,
+/// `None` if the padding isn't made of up to 4 zero bits.
+fn witness_program(values: &[u8]) -> Option<Vec<u8>> {
+    let mut program = Vec::with_capacity(values.len() * 5 / 8);
+    let mut acc = 0u32;
+    let mut bits = 0;
+    for &value in values {
+        acc = (acc << 5) | value as u32;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            program.push((acc >> bits) as u8);
+        }
+    }
+
+    Some(program).filter(|_| bits < 5 && acc & ((1 << bits) - 1) == 0)
+}
+
+/// Checksum of BIP 173: 1 for valid bech32, `0x2bc830a3` for valid bech32m.
+fn bech32_polymod(values: &[u8]) -> u32 {
+    const GENERATOR: [u32; 5] = [
+        0x3b6a_57b2,
+        0x2650_8e6d,
+        0x1ea1_19fa,
+        0x3d42_33dd,
+        0x2a14_62b3,
+    ];
+
+    values.iter().fold(1, |chk, &value| {
+        let top = chk >> 25;
+        let chk = ((chk & 0x1ff_ffff) << 5) ^ value as u32;
+        (0..5)
+            .filter(|i| (top >> i) & 1 == 1)
+            .fold(chk, |chk, i| chk ^ GENERATOR[i])
+    })
 }
 
 impl<'a> From<&BtcAddress<'a>> for String {
@@ -28,3 +296,773 @@ impl<'a> From<&BtcAddress<'a>> for Value {
         json!(address.address)
     }
 }
+
+/// Represents btc address returned by the daemon
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(transparent)]
+pub struct OwnedBtcAddress {
+    pub address: String,
+}
+
+impl OwnedBtcAddress {
+    /// Create a new address from String
+    pub fn new(address: String) -> Self {
+        Self { address }
+    }
+
+    /// Borrow as `BtcAddress` to pass it back into client methods
+    pub fn as_btc_address(&self) -> BtcAddress<'_> {
+        BtcAddress::new(&self.address)
+    }
+}
+
+impl<'a> From<&BtcAddress<'a>> for OwnedBtcAddress {
+    fn from(address: &BtcAddress<'a>) -> Self {
+        Self::new(address.address.to_string())
+    }
+}
+
+/// Amount of BTC, no finer than one satoshi.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Amount(Decimal);
+
+impl Amount {
+    /// Create amount rejecting negative values and values with more
+    /// than 8 decimal places, which the daemon would silently round.
+    pub fn from_btc_checked(btc: Decimal) -> Result<Self> {
+        let btc = btc.normalize();
+
+        if btc.is_sign_negative() && !btc.is_zero() {
+            return Err(ElectrumRpcError::InvalidParams(format!(
+                "amount {} is negative",
+                btc
+            )));
+        }
+
+        if btc.scale() > BTC_DECIMAL_PLACES {
+            return Err(ElectrumRpcError::InvalidParams(format!(
+                "amount {} has more than {} decimal places",
+                btc, BTC_DECIMAL_PLACES
+            )));
+        }
+
+        Ok(Self(btc))
+    }
+
+    pub fn as_btc(&self) -> Decimal {
+        self.0
+    }
+}
+
+impl FromStr for Amount {
+    type Err = ElectrumRpcError;
+
+    /// Parse a plain decimal BTC amount like `0.0015`, with a dot as the
+    /// decimal point. Locale-specific forms (`1,5`, `1 000`), signs and
+    /// exponents are rejected.
+    fn from_str(s: &str) -> Result<Self> {
+        let (integer, fraction) = match s.split_once('.') {
+            Some((integer, fraction)) => (integer, Some(fraction)),
+            None => (s, None),
+        };
+        let is_digits = |part: &str| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit());
+
+        if !is_digits(integer) || !fraction.is_none_or(is_digits) {
+            return Err(ElectrumRpcError::InvalidParams(format!(
+                "amount {:?} is not a plain decimal",
+                s
+            )));
+        }
+
+        let btc = Decimal::from_str(s)
+            .map_err(|e| ElectrumRpcError::InvalidParams(format!("amount {:?}: {}", s, e)))?;
+        Self::from_btc_checked(btc)
+    }
+}
+
+/// Amount to pay in BTC, or `Max` to spend everything the wallet can,
+/// e.g. to sweep it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PayAmount {
+    Btc(Decimal),
+    Max,
+}
+
+impl PayAmount {
+    /// Check a `Btc` amount with [`Amount::from_btc_checked`].
+    pub fn checked(self) -> Result<Self> {
+        match self {
+            Self::Btc(btc) => Ok(Self::Btc(Amount::from_btc_checked(btc)?.as_btc())),
+            Self::Max => Ok(Self::Max),
+        }
+    }
+}
+
+impl From<Decimal> for PayAmount {
+    fn from(btc: Decimal) -> Self {
+        Self::Btc(btc)
+    }
+}
+
+impl From<Amount> for PayAmount {
+    fn from(amount: Amount) -> Self {
+        Self::Btc(amount.as_btc())
+    }
+}
+
+/// JSON number, or `"!"` for `Max`.
+impl From<PayAmount> for Value {
+    fn from(amount: PayAmount) -> Self {
+        match amount {
+            PayAmount::Btc(btc) => btc_value(btc),
+            PayAmount::Max => json!("!"),
+        }
+    }
+}
+
+impl Serialize for PayAmount {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        Value::from(*self).serialize(serializer)
+    }
+}
+
+/// `btc` as a JSON number. BTC amounts have at most 16 significant digits,
+/// so the number parses back to the same decimal.
+pub(crate) fn btc_value(btc: Decimal) -> Value {
+    btc.to_f64().map_or(Value::Null, Value::from)
+}
+
+/// Transaction id, 64 hex characters.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Txid(String);
+
+impl Txid {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromStr for Txid {
+    type Err = ElectrumRpcError;
+
+    /// Parse a txid, normalized to lowercase.
+    fn from_str(s: &str) -> Result<Self> {
+        if s.len() != 64 || !s.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(ElectrumRpcError::InvalidTxid(s.to_string()));
+        }
+
+        Ok(Self(s.to_ascii_lowercase()))
+    }
+}
+
+impl std::fmt::Display for Txid {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Fee rate, stored in sat/vByte. Electrum reports fee rates in sat/vB
+/// in some commands and sat/kvB in others; keep them in this type to avoid
+/// mixing the two up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FeeRate(Decimal);
+
+impl FeeRate {
+    pub fn from_sat_per_vb(sat_per_vb: Decimal) -> Self {
+        Self(sat_per_vb.normalize())
+    }
+
+    pub fn from_sat_per_kvb(sat_per_kvb: Decimal) -> Self {
+        Self::from_sat_per_vb(sat_per_kvb / Decimal::from(1000))
+    }
+
+    pub fn as_sat_per_vb(&self) -> Decimal {
+        self.0
+    }
+
+    pub fn as_sat_per_kvb(&self) -> Decimal {
+        (self.0 * Decimal::from(1000)).normalize()
+    }
+
+    /// Read the result of `getfeerate`: a bare number in sat/kvB, or a string
+    /// with an explicit `sat/vB` or `sat/kvB` unit.
+    pub fn from_getfeerate(result: &Value) -> Result<Self> {
+        match result {
+            Value::Number(number) => Decimal::from_str(&number.to_string())
+                .map(Self::from_sat_per_kvb)
+                .map_err(|e| {
+                    ElectrumRpcError::InvalidParams(format!("fee rate {}: {}", number, e))
+                }),
+            Value::String(s) => s.parse(),
+            other => Err(ElectrumRpcError::InvalidParams(format!(
+                "fee rate {} is neither a number nor a string",
+                other
+            ))),
+        }
+    }
+}
+
+impl FromStr for FeeRate {
+    type Err = ElectrumRpcError;
+
+    /// Parse `12.5 sat/vB` or `12500 sat/kvB`; the unit is required.
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = |reason: String| {
+            ElectrumRpcError::InvalidParams(format!("fee rate {:?}: {}", s, reason))
+        };
+
+        let (value, unit) = s
+            .trim()
+            .split_once(char::is_whitespace)
+            .ok_or_else(|| invalid("missing unit".to_string()))?;
+        let value = Decimal::from_str(value).map_err(|e| invalid(e.to_string()))?;
+
+        match unit.trim() {
+            "sat/vB" | "sat/vbyte" => Ok(Self::from_sat_per_vb(value)),
+            "sat/kvB" | "sat/kvbyte" => Ok(Self::from_sat_per_kvb(value)),
+            unit => Err(invalid(format!("unknown unit {:?}", unit))),
+        }
+    }
+}
+
+impl std::fmt::Display for FeeRate {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} sat/vB", self.0)
+    }
+}
+
+/// Script expressions allowed at the top level of an output descriptor.
+const DESCRIPTOR_FUNCTIONS: [&str; 10] = [
+    "pkh",
+    "wpkh",
+    "sh",
+    "wsh",
+    "tr",
+    "combo",
+    "multi",
+    "sortedmulti",
+    "addr",
+    "raw",
+];
+
+/// Minimal syntax check of an output script descriptor, e.g.
+/// `wpkh([d34db33f/84h/0h/0h]xpub.../0/*)#checksum`.
+/// Key material and the checksum value itself are not verified.
+pub(crate) fn validate_descriptor(descriptor: &str) -> Result<()> {
+    let invalid = |reason: &str| {
+        Err(ElectrumRpcError::InvalidParams(format!(
+            "descriptor {:?} {}",
+            descriptor, reason
+        )))
+    };
+
+    let (script, checksum) = match descriptor.split_once('#') {
+        Some((script, checksum)) => (script, Some(checksum)),
+        None => (descriptor, None),
+    };
+
+    if let Some(checksum) = checksum {
+        if checksum.len() != 8 || !checksum.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return invalid("has malformed checksum");
+        }
+    }
+
+    let function = match script.find('(') {
+        Some(open) => &script[..open],
+        None => return invalid("is not a script expression"),
+    };
+    if !DESCRIPTOR_FUNCTIONS.contains(&function) {
+        return invalid("has unknown script function");
+    }
+
+    let mut depth = 0usize;
+    for (i, c) in script.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 0 => return invalid("has unbalanced parentheses"),
+            ')' => {
+                depth -= 1;
+                if depth == 0 && i != script.len() - 1 {
+                    return invalid("has trailing characters");
+                }
+            }
+            c if c.is_whitespace() => return invalid("contains whitespace"),
+            _ => {}
+        }
+    }
+    if depth != 0 || !script.ends_with(')') {
+        return invalid("has unbalanced parentheses");
+    }
+
+    Ok(())
+}
+
+/// Fields of a BOLT11 Lightning invoice.
+#[cfg(feature = "lightning")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bolt11Details {
+    /// Network prefix, e.g. `bc`, `tb`, `bcrt`.
+    pub currency: String,
+    /// Requested amount in BTC, `None` for any amount.
+    pub amount: Option<Decimal>,
+    /// Creation time, seconds since unix epoch.
+    pub timestamp: u64,
+    /// Hex encoded payment hash.
+    pub payment_hash: String,
+    pub description: Option<String>,
+    /// Seconds after `timestamp` the invoice expires.
+    pub expiry: u64,
+}
+
+/// Expiry of an invoice without the `x` field, in secs.
+#[cfg(feature = "lightning")]
+const BOLT11_DEFAULT_EXPIRY: u64 = 3600;
+
+/// Decode a BOLT11 invoice locally, checking its bech32 checksum and layout.
+/// The signature isn't verified.
+#[cfg(feature = "lightning")]
+pub fn decode_bolt11(invoice: &str) -> Result<Bolt11Details> {
+    use bech32::{FromBase32, Variant};
+
+    // Groups of 5 bits taken by the timestamp and by the signature.
+    const TIMESTAMP_LEN: usize = 7;
+    const SIGNATURE_LEN: usize = 104;
+
+    let invalid = |reason: &str| ElectrumRpcError::InvalidInvoice(reason.to_string());
+    let to_int = |groups: &[bech32::u5]| {
+        groups
+            .iter()
+            .fold(0u64, |acc, group| acc << 5 | u64::from(group.to_u8()))
+    };
+
+    let (hrp, data, variant) =
+        bech32::decode(invoice).map_err(|e| ElectrumRpcError::InvalidInvoice(e.to_string()))?;
+    if variant != Variant::Bech32 {
+        return Err(invalid("not bech32 encoded"));
+    }
+
+    let prefix = hrp
+        .strip_prefix("ln")
+        .ok_or_else(|| invalid("missing ln prefix"))?;
+    let amount_start = prefix
+        .find(|c: char| c.is_ascii_digit())
+        .unwrap_or(prefix.len());
+    let (currency, amount) = prefix.split_at(amount_start);
+    if currency.is_empty() {
+        return Err(invalid("missing currency"));
+    }
+
+    if data.len() < TIMESTAMP_LEN + SIGNATURE_LEN {
+        return Err(invalid("too short"));
+    }
+    let timestamp = to_int(&data[..TIMESTAMP_LEN]);
+    let mut fields = &data[TIMESTAMP_LEN..data.len() - SIGNATURE_LEN];
+
+    let mut payment_hash = None;
+    let mut description = None;
+    let mut expiry = BOLT11_DEFAULT_EXPIRY;
+    while !fields.is_empty() {
+        if fields.len() < 3 {
+            return Err(invalid("truncated field"));
+        }
+        let len = to_int(&fields[1..3]) as usize;
+        let value = fields
+            .get(3..3 + len)
+            .ok_or_else(|| invalid("truncated field"))?;
+
+        match fields[0].to_u8() {
+            // `p`, fields of unexpected length are skipped per BOLT11
+            1 if len == 52 => {
+                let hash =
+                    Vec::<u8>::from_base32(value).map_err(|_| invalid("bad payment hash"))?;
+                payment_hash = Some(hash.iter().map(|byte| format!("{:02x}", byte)).collect());
+            }
+            // `d`
+            13 => {
+                let text = Vec::<u8>::from_base32(value).map_err(|_| invalid("bad description"))?;
+                let text = String::from_utf8(text).map_err(|_| invalid("description not utf-8"))?;
+                description = Some(text);
+            }
+            // `x`
+            6 => expiry = to_int(value),
+            _ => {}
+        }
+
+        fields = &fields[3 + len..];
+    }
+
+    Ok(Bolt11Details {
+        currency: currency.to_string(),
+        amount: parse_bolt11_amount(amount)?,
+        timestamp,
+        payment_hash: payment_hash.ok_or_else(|| invalid("missing payment hash"))?,
+        description,
+        expiry,
+    })
+}
+
+/// Amount of the human readable part, e.g. `2500u`, in BTC.
+#[cfg(feature = "lightning")]
+fn parse_bolt11_amount(amount: &str) -> Result<Option<Decimal>> {
+    if amount.is_empty() {
+        return Ok(None);
+    }
+
+    let invalid = || ElectrumRpcError::InvalidInvoice(format!("bad amount {:?}", amount));
+    let (digits, scale) = match amount.char_indices().last() {
+        Some((i, 'm')) => (&amount[..i], 3),
+        Some((i, 'u')) => (&amount[..i], 6),
+        Some((i, 'n')) => (&amount[..i], 9),
+        Some((i, 'p')) => (&amount[..i], 12),
+        _ => (amount, 0),
+    };
+
+    let value = digits.parse::<i64>().map_err(|_| invalid())?;
+    // pico-btc amounts must be whole milli-satoshis
+    if scale == 12 && value % 10 != 0 {
+        return Err(invalid());
+    }
+
+    Ok(Some(Decimal::new(value, scale).normalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn address_parse_valid() {
+        for address in [
+            "1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa",
+            "3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy",
+            "mipcBbFg9gMiCh81Kj8tqqdgoZub1ZJRfn",
+            "2MzQwSSnBHWHqSAqtTVQ6v47XtaisrJa1Vc",
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+            "BC1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KV8F3T4",
+            "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0",
+            "tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn",
+            "BC1SW50QGDZ25J",
+        ] {
+            assert_eq!(BtcAddress::parse(address).unwrap().address, address);
+        }
+    }
+
+    #[test]
+    fn address_parse_garbage() {
+        for address in [
+            "",
+            "hello",
+            "1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfN0",
+            "11A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa",
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t5",
+            "bc1Qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+            "ltc1qw508d6qejxtdg4y5r3zarvary0c5xw7kgmn4n9",
+            // Base58 checksum of the last character changed.
+            "1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNb",
+            // BIP 173/350 invalid program lengths.
+            "bc1rw5uspcuh",
+            "bc10w508d6qejxtdg4y5r3zarvary0c5xw7kw508d6qejxtdg4y5r3zarvary0c5xw7kw5rljs90",
+            "BC1QR508D6QEJXTDG4Y5R3ZARVARYV98GJ9P",
+        ] {
+            assert!(matches!(
+                BtcAddress::parse(address),
+                Err(ElectrumRpcError::InvalidAddress(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn address_types() {
+        let cases = [
+            ("1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa", AddressType::P2pkh),
+            ("mipcBbFg9gMiCh81Kj8tqqdgoZub1ZJRfn", AddressType::P2pkh),
+            ("3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy", AddressType::P2sh),
+            (
+                "tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn",
+                AddressType::P2wpkh,
+            ),
+            (
+                "bc1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3qccfmv3",
+                AddressType::P2wsh,
+            ),
+            (
+                "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0",
+                AddressType::P2tr,
+            ),
+        ];
+
+        for (address, expected) in cases {
+            assert_eq!(AddressType::of(address).unwrap(), expected, "{}", address);
+        }
+    }
+
+    #[test]
+    fn dust_thresholds() {
+        assert!(!is_dust(Decimal::new(294, 8), AddressType::P2wpkh));
+        assert!(is_dust(Decimal::new(293, 8), AddressType::P2wpkh));
+        assert!(!is_dust(Decimal::new(546, 8), AddressType::P2pkh));
+        assert!(is_dust(Decimal::new(545, 8), AddressType::P2pkh));
+        assert!(is_dust(Decimal::new(300, 8), AddressType::P2pkh));
+    }
+
+    #[test]
+    fn address_parse_for_network() {
+        let cases = [
+            ("1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa", Network::Mainnet),
+            ("3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy", Network::Mainnet),
+            (
+                "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+                Network::Mainnet,
+            ),
+            ("mipcBbFg9gMiCh81Kj8tqqdgoZub1ZJRfn", Network::Testnet),
+            ("2MzQwSSnBHWHqSAqtTVQ6v47XtaisrJa1Vc", Network::Testnet),
+            (
+                "tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn",
+                Network::Testnet,
+            ),
+            ("mipcBbFg9gMiCh81Kj8tqqdgoZub1ZJRfn", Network::Regtest),
+        ];
+        for (address, network) in cases {
+            assert!(BtcAddress::parse_for_network(address, network).is_ok());
+        }
+
+        let res = BtcAddress::parse_for_network(
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+            Network::Testnet,
+        );
+        assert!(matches!(
+            res,
+            Err(ElectrumRpcError::NetworkMismatch {
+                network: Network::Testnet,
+                ..
+            })
+        ));
+        let res = BtcAddress::parse_for_network(
+            "tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn",
+            Network::Regtest,
+        );
+        assert!(matches!(res, Err(ElectrumRpcError::NetworkMismatch { .. })));
+        let res = BtcAddress::parse_for_network("hello", Network::Mainnet);
+        assert!(matches!(res, Err(ElectrumRpcError::InvalidAddress(_))));
+    }
+
+    #[test]
+    fn txid_hex_64() {
+        let txid: Txid = "EAF3E55E521431BCD18294B8A536A966F5FBDC9F7701CDBAA877CEC9E95B8CB5"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            txid.as_str(),
+            "eaf3e55e521431bcd18294b8a536a966f5fbdc9f7701cdbaa877cec9e95b8cb5"
+        );
+
+        for malformed in [
+            "",
+            "eaf3e55e",
+            "eaf3e55e521431bcd18294b8a536a966f5fbdc9f7701cdbaa877cec9e95b8cb5aa",
+            "zaf3e55e521431bcd18294b8a536a966f5fbdc9f7701cdbaa877cec9e95b8cb5",
+        ] {
+            assert!(matches!(
+                malformed.parse::<Txid>(),
+                Err(ElectrumRpcError::InvalidTxid(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn fee_rate_units_normalized() {
+        let expected = FeeRate::from_sat_per_vb(Decimal::new(125, 1));
+
+        assert_eq!(FeeRate::from_sat_per_kvb(Decimal::from(12500)), expected);
+        assert_eq!("12.5 sat/vB".parse::<FeeRate>().unwrap(), expected);
+        assert_eq!("12500 sat/kvB".parse::<FeeRate>().unwrap(), expected);
+        assert_eq!(FeeRate::from_getfeerate(&json!(12500)).unwrap(), expected);
+        assert_eq!(
+            FeeRate::from_getfeerate(&json!("12.5 sat/vB")).unwrap(),
+            expected
+        );
+
+        assert_eq!(expected.as_sat_per_kvb(), Decimal::from(12500));
+        assert_eq!(expected.to_string(), "12.5 sat/vB");
+        assert!("12.5".parse::<FeeRate>().is_err());
+        assert!("12.5 BTC/kB".parse::<FeeRate>().is_err());
+        assert!(FeeRate::from_getfeerate(&Value::Null).is_err());
+    }
+
+    #[test]
+    fn amount_eight_decimal_places() {
+        let amount = Amount::from_btc_checked(Decimal::new(12345678, 8)).unwrap();
+        assert_eq!(amount.as_btc(), Decimal::new(12345678, 8));
+
+        let amount = Amount::from_btc_checked(Decimal::new(1_000_000_000, 9)).unwrap();
+        assert_eq!(amount.as_btc(), Decimal::new(1, 0));
+    }
+
+    #[test]
+    fn amount_nine_decimal_places() {
+        let res = Amount::from_btc_checked(Decimal::new(123456789, 9));
+        assert!(matches!(res, Err(ElectrumRpcError::InvalidParams(_))));
+    }
+
+    #[test]
+    fn amount_negative() {
+        let res = Amount::from_btc_checked(Decimal::new(-1, 3));
+        assert!(matches!(res, Err(ElectrumRpcError::InvalidParams(_))));
+    }
+
+    #[test]
+    fn amount_from_str() {
+        let accepted = [
+            ("1", Decimal::new(1, 0)),
+            ("0.0015", Decimal::new(15, 4)),
+            ("21000000.00000000", Decimal::new(21_000_000, 0)),
+            ("0.00000001", Decimal::new(1, 8)),
+        ];
+
+        for (input, expected) in accepted.iter() {
+            let amount: Amount = input.parse().unwrap();
+            assert_eq!(amount.as_btc(), *expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn pay_amount_serialize() {
+        for (btc, expected) in [
+            (Decimal::new(15, 4), "0.0015"),
+            (Decimal::new(1, 8), "1e-8"),
+            (Decimal::new(2, 0), "2.0"),
+            (Decimal::new(2099999999999999, 8), "20999999.99999999"),
+        ] {
+            let actual = serde_json::to_string(&PayAmount::from(btc)).unwrap();
+            assert_eq!(actual, expected);
+        }
+
+        assert_eq!(serde_json::to_string(&PayAmount::Max).unwrap(), r#""!""#);
+    }
+
+    #[test]
+    fn pay_amount_checked() {
+        let amount = PayAmount::from(Decimal::new(150, 5)).checked().unwrap();
+        assert_eq!(amount, PayAmount::Btc(Decimal::new(15, 4)));
+        assert_eq!(PayAmount::Max.checked().unwrap(), PayAmount::Max);
+
+        let res = PayAmount::from(Decimal::new(1, 9)).checked();
+        assert!(matches!(res, Err(ElectrumRpcError::InvalidParams(_))));
+    }
+
+    #[test]
+    fn amount_from_str_rejected() {
+        let rejected = [
+            "",
+            "1,5",
+            "1,000.5",
+            "1 000",
+            " 1",
+            "1.",
+            ".5",
+            "+1",
+            "-1",
+            "1e3",
+            "0.000000001",
+        ];
+
+        for input in rejected.iter() {
+            let res = input.parse::<Amount>();
+            assert!(
+                matches!(res, Err(ElectrumRpcError::InvalidParams(_))),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn descriptor_valid() {
+        let descriptors = [
+            "wpkh([d34db33f/84h/0h/0h]tpubD6NzVbkrYhZ4WaWSyoBvQwbpLkojyoTZPRsgXELWz3Popb3qkjcJyJUGLnL4qHHoQvao8ESaAstxYSnhyswJ76uZPStJRJCTKvosUCJZL5B/0/*)",
+            "sh(wpkh(03a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd))#ggrsrxfy",
+            "wsh(sortedmulti(1,xpub1/0/*,xpub2/0/*))",
+            "tr(c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5)",
+        ];
+
+        for descriptor in descriptors.iter() {
+            assert!(validate_descriptor(descriptor).is_ok(), "{}", descriptor);
+        }
+    }
+
+    #[test]
+    fn descriptor_malformed() {
+        let descriptors = [
+            "",
+            "xpub6CUGRUonZSQ4TWtTMmzXdrXDtypWKiKrhko4egpiMZbpiaQL2jkwSB1icqYh2cfDfVxdx4df189oLKnC5fSwqPfgyP3hooxujYzAu3fDVmz",
+            "wpkh(02f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9",
+            "wpkh(02f9308a)extra",
+            "foo(02f9308a)",
+            "wpkh(02f9308a)#abc",
+        ];
+
+        for descriptor in descriptors.iter() {
+            let res = validate_descriptor(descriptor);
+            assert!(
+                matches!(res, Err(ElectrumRpcError::InvalidParams(_))),
+                "{}",
+                descriptor
+            );
+        }
+    }
+
+    #[cfg(feature = "lightning")]
+    const BOLT11_COFFEE: &str = "lntb2500u1pvjluezpp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqdq5xysxxatsyp3k7enxv4jsxqzpuqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqzanpwm";
+
+    #[cfg(feature = "lightning")]
+    #[test]
+    fn bolt11_testnet_invoice() {
+        let details = decode_bolt11(BOLT11_COFFEE).unwrap();
+
+        assert_eq!(details.currency, "tb");
+        assert_eq!(details.amount, Some(Decimal::new(25, 4)));
+        assert_eq!(details.timestamp, 1496314658);
+        assert_eq!(
+            details.payment_hash,
+            "0001020304050607080900010203040506070809000102030405060708090102"
+        );
+        assert_eq!(details.description.as_deref(), Some("1 cup coffee"));
+        assert_eq!(details.expiry, 60);
+    }
+
+    #[cfg(feature = "lightning")]
+    #[test]
+    fn bolt11_any_amount_default_expiry() {
+        let invoice = "lntb1pvjluezpp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqhp5qqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqjjfqp9";
+
+        let details = decode_bolt11(invoice).unwrap();
+
+        assert_eq!(details.amount, None);
+        assert_eq!(details.description, None);
+        assert_eq!(details.expiry, 3600);
+    }
+
+    #[cfg(feature = "lightning")]
+    #[test]
+    fn bolt11_rejected() {
+        let mut corrupted = BOLT11_COFFEE.to_string();
+        corrupted.replace_range(20..21, "z");
+
+        for invoice in [
+            corrupted.as_str(),
+            "tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn",
+        ]
+        .iter()
+        {
+            let res = decode_bolt11(invoice);
+            assert!(
+                matches!(res, Err(ElectrumRpcError::InvalidInvoice(_))),
+                "{}",
+                invoice
+            );
+        }
+    }
+}
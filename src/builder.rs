@@ -0,0 +1,387 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicBool, AtomicU64};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use hyper::client::HttpConnector;
+use hyper::{Client, Uri};
+use lru::LruCache;
+use serde_json::Value;
+use tokio::runtime::Handle;
+use tokio::sync::Semaphore;
+
+use crate::btc::Network;
+use crate::constants::MAX_RETRY_DELAY;
+use crate::error::{ElectrumRpcError, Result};
+use crate::keepalive;
+#[cfg(feature = "metrics")]
+use crate::metrics::Metrics;
+use crate::responses::MerkleProof;
+use crate::retry::RetryPolicy;
+use crate::secret::{self, SecretHeader};
+#[cfg(feature = "tls")]
+use crate::tls::PinnedCertVerifier;
+use crate::{Connector, Electrum, EmptyParams};
+
+/// Source of the wallet password, called on each spend.
+pub(crate) type PasswordProvider = Arc<dyn Fn() -> String + Send + Sync>;
+
+/// Source of json-rpc request ids, called for each request.
+pub(crate) type IdGenerator = Arc<dyn Fn() -> Value + Send + Sync>;
+
+/// Merkle proofs keyed by `(txid, height)`.
+pub(crate) type MerkleCache = Arc<Mutex<LruCache<(String, u64), MerkleProof>>>;
+
+/// Builder for `Electrum` client with optional connection settings.
+///
+/// # Examples
+/// ```
+/// # use electrum_jsonrpc::Electrum;
+/// # use std::time::Duration;
+/// let client = Electrum::builder(
+///     "dummy_login".to_string(),
+///     "dummy_password".to_string(),
+///     "http://127.0.0.1:7000".to_string(),
+/// )
+/// .with_max_connections(4)
+/// .with_pool_wait(Duration::from_secs(5))
+/// .build()
+/// .unwrap();
+/// ```
+pub struct ElectrumBuilder {
+    login: String,
+    password: String,
+    address: String,
+    max_connections: Option<usize>,
+    pool_wait: Option<Duration>,
+    password_provider: Option<PasswordProvider>,
+    empty_params: EmptyParams,
+    empty_params_by_method: HashMap<String, EmptyParams>,
+    merkle_cache: Option<NonZeroUsize>,
+    http2_prior_knowledge: bool,
+    wallet_preflight: bool,
+    local_address: Option<IpAddr>,
+    retries: Option<RetryPolicy>,
+    tcp_nodelay: bool,
+    max_batch: usize,
+    timeout: Option<Duration>,
+    strict_parsing: bool,
+    id_generator: Option<IdGenerator>,
+    network: Option<Network>,
+    dust_check: bool,
+    keepalive: Option<Duration>,
+    #[cfg(feature = "tls")]
+    pinned_cert: Option<[u8; 32]>,
+}
+
+impl ElectrumBuilder {
+    pub fn new(login: String, password: String, address: String) -> Self {
+        Self {
+            login,
+            password,
+            address,
+            max_connections: None,
+            pool_wait: None,
+            password_provider: None,
+            empty_params: EmptyParams::Object,
+            empty_params_by_method: HashMap::new(),
+            merkle_cache: None,
+            http2_prior_knowledge: false,
+            wallet_preflight: false,
+            local_address: None,
+            retries: None,
+            tcp_nodelay: true,
+            max_batch: 0,
+            timeout: None,
+            strict_parsing: false,
+            id_generator: None,
+            network: None,
+            dust_check: false,
+            keepalive: None,
+            #[cfg(feature = "tls")]
+            pinned_cert: None,
+        }
+    }
+
+    /// Limit the number of requests in flight to the daemon.
+    pub fn with_max_connections(mut self, max: usize) -> Self {
+        self.max_connections = Some(max);
+        self
+    }
+
+    /// How long a call waits for a free connection before failing
+    /// with `ElectrumRpcError::ConnectionPoolBusy`.
+    /// Only has effect together with `with_max_connections`.
+    pub fn with_pool_wait(mut self, wait: Duration) -> Self {
+        self.pool_wait = Some(wait);
+        self
+    }
+
+    /// Fetch the wallet password on demand (e.g. from an OS keychain) instead of
+    /// keeping it in the client. The provider is called only for the calls
+    /// spending from the wallet and the password is dropped once the request is sent.
+    pub fn with_password_provider<F>(mut self, provider: F) -> Self
+    where
+        F: Fn() -> String + Send + Sync + 'static,
+    {
+        self.password_provider = Some(Arc::new(provider));
+        self
+    }
+
+    /// Form of `params` for calls without params, for daemons or proxies
+    /// that accept only one of them.
+    pub fn with_empty_params(mut self, empty_params: EmptyParams) -> Self {
+        self.empty_params = empty_params;
+        self
+    }
+
+    /// Form of `params` for calls of daemon command `method`, e.g.
+    /// `"getinfo"`, without params, taking precedence over `with_empty_params`.
+    pub fn with_empty_params_for(mut self, method: &str, empty_params: EmptyParams) -> Self {
+        self.empty_params_by_method
+            .insert(method.to_string(), empty_params);
+        self
+    }
+
+    /// Keep up to `capacity` last merkle proofs fetched by `get_merkle`.
+    /// Zero capacity leaves the cache disabled.
+    pub fn with_merkle_cache(mut self, capacity: usize) -> Self {
+        self.merkle_cache = NonZeroUsize::new(capacity);
+        self
+    }
+
+    /// Speak HTTP/2 without upgrade negotiation, e.g. to an HTTP/2 gateway
+    /// in front of the daemon. Electrum's own server speaks HTTP/1 only,
+    /// which stays the default.
+    pub fn with_http2_prior_knowledge(mut self, enabled: bool) -> Self {
+        self.http2_prior_knowledge = enabled;
+        self
+    }
+
+    /// Check with `list_wallets` that a wallet is loaded before spending or
+    /// adding payment requests, failing early with
+    /// `ElectrumErrorCode::WalletNotLoaded` instead of the daemon's raw error.
+    /// Costs an extra round trip per such call.
+    pub fn with_wallet_preflight(mut self, enabled: bool) -> Self {
+        self.wallet_preflight = enabled;
+        self
+    }
+
+    /// Bind outgoing connections to `local_address`, for multi-homed hosts
+    /// where the daemon accepts connections from a particular source ip only.
+    pub fn with_local_address(mut self, local_address: IpAddr) -> Self {
+        self.local_address = Some(local_address);
+        self
+    }
+
+    /// Retry calls rejected with `429 Too Many Requests` up to `max` times.
+    /// Waits as long as the `Retry-After` header says, or `base_delay`
+    /// doubled on each attempt when the header is missing.
+    /// Calls that couldn't connect, e.g. while the daemon restarts, are
+    /// retried too, with `base_delay` doubled on each attempt and jittered.
+    /// Calls failing once connected aren't, as the daemon may have run them.
+    /// No delay exceeds one minute. Once retries run out a rate-limited call
+    /// fails with `ElectrumRpcError::RateLimited`.
+    pub fn with_retries(mut self, max: u32, base_delay: Duration) -> Self {
+        self.retries = Some(RetryPolicy {
+            max,
+            base_delay,
+            max_delay: MAX_RETRY_DELAY,
+        });
+        self
+    }
+
+    /// Set `TCP_NODELAY` on connections to the daemon. Enabled by default
+    /// so small json-rpc requests aren't held back by Nagle's algorithm.
+    pub fn with_tcp_nodelay(mut self, enabled: bool) -> Self {
+        self.tcp_nodelay = enabled;
+        self
+    }
+
+    /// Split batched calls into json-rpc batches of at most `max` calls,
+    /// sent as separate requests, e.g. to stay below a proxy's body size limit.
+    /// Zero leaves batches unsplit.
+    pub fn with_max_batch(mut self, max: usize) -> Self {
+        self.max_batch = max;
+        self
+    }
+
+    /// Fail calls the daemon doesn't answer within `timeout` with
+    /// `ElectrumRpcError::Timeout`, retries and reading the reply included.
+    /// Calls wait indefinitely by default.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Fail typed calls whose result has fields the result type doesn't know,
+    /// e.g. to catch changes of the daemon's responses in test environments.
+    /// Unknown fields are ignored by default. Public result types only:
+    /// helpers reading a few fields of a daemon reply, e.g.
+    /// `transaction_report`, still ignore the rest.
+    pub fn with_strict_parsing(mut self, enabled: bool) -> Self {
+        self.strict_parsing = enabled;
+        self
+    }
+
+    /// Take json-rpc request ids from `generator`, e.g. UUIDs or correlation ids
+    /// of the caller, numeric or string. Ids must be unique within a batch.
+    pub fn with_id_generator<F>(mut self, generator: F) -> Self
+    where
+        F: Fn() -> Value + Send + Sync + 'static,
+    {
+        self.id_generator = Some(Arc::new(generator));
+        self
+    }
+
+    /// Network the daemon runs on. Payments to addresses of another network
+    /// then fail with `ElectrumRpcError::NetworkMismatch` before reaching the daemon.
+    /// Unset by default, leaving addresses unchecked.
+    pub fn with_network(mut self, network: Network) -> Self {
+        self.network = Some(network);
+        self
+    }
+
+    /// Reject payments with outputs below the dust threshold of their address
+    /// type with `ElectrumErrorCode::DustOutput` before reaching the daemon.
+    /// Disabled by default.
+    pub fn with_dust_check(mut self, enabled: bool) -> Self {
+        self.dust_check = enabled;
+        self
+    }
+
+    /// Ping the daemon with a `version` call every `interval` from a background
+    /// task, keeping pooled connections warm. `Electrum::is_healthy` turns
+    /// `false` when a ping gets no answer within `interval`. The task stops
+    /// with the last clone of the client. Requires a tokio runtime to build.
+    pub fn with_keepalive(mut self, interval: Duration) -> Self {
+        self.keepalive = Some(interval);
+        self
+    }
+
+    /// Accept only the daemon certificate with SHA-256 `fingerprint` instead
+    /// of certificates issued by the webpki roots, e.g. for a daemon with a
+    /// self-signed certificate. Connections to any other certificate fail,
+    /// and so does building a client for a plain `http://` address.
+    #[cfg(feature = "tls")]
+    pub fn with_pinned_cert(mut self, fingerprint: [u8; 32]) -> Self {
+        self.pinned_cert = Some(fingerprint);
+        self
+    }
+
+    pub fn build(self) -> Result<Electrum> {
+        if self.keepalive.is_some() && Handle::try_current().is_err() {
+            return Err(ElectrumRpcError::InvalidParams(
+                "keepalive needs a tokio runtime to run on".to_string(),
+            ));
+        }
+
+        let address = self.address.parse::<Uri>()?;
+        let mut connector = HttpConnector::new();
+        connector.set_local_address(self.local_address);
+        connector.set_nodelay(self.tcp_nodelay);
+        let connector = self.wrap_connector(connector, &address)?;
+
+        let plain = format!("{}:{}", self.login, self.password);
+        let credentials = base64::encode(&plain);
+        let auth = SecretHeader::new(format!("Basic {}", credentials))?;
+        secret::wipe(plain);
+        secret::wipe(credentials);
+        secret::wipe(self.password);
+
+        let mut client = Client::builder();
+        if let Some(max) = self.max_connections {
+            client.pool_max_idle_per_host(max);
+        }
+        client.http2_only(self.http2_prior_knowledge);
+
+        let mut electrum = Electrum {
+            auth,
+            address,
+            client: client.build(connector),
+            connections: self
+                .max_connections
+                .map(|max| Arc::new(Semaphore::new(max))),
+            pool_wait: self.pool_wait,
+            password_provider: self.password_provider,
+            empty_params: self.empty_params,
+            empty_params_by_method: self.empty_params_by_method,
+            merkle_cache: self
+                .merkle_cache
+                .map(|capacity| Arc::new(Mutex::new(LruCache::new(capacity)))),
+            wallet_preflight: self.wallet_preflight,
+            retries: self.retries,
+            max_batch: match self.max_batch {
+                0 => usize::MAX,
+                max => max,
+            },
+            timeout: self.timeout,
+            strict_parsing: self.strict_parsing,
+            id_generator: self.id_generator,
+            next_id: Arc::new(AtomicU64::new(0)),
+            network: self.network,
+            dust_check: self.dust_check,
+            healthy: Arc::new(AtomicBool::new(true)),
+            keepalive: None,
+            #[cfg(feature = "metrics")]
+            metrics: Arc::new(Metrics::default()),
+        };
+
+        if let Some(interval) = self.keepalive {
+            let guard = keepalive::spawn(electrum.clone(), interval, electrum.healthy.clone());
+            electrum.keepalive = Some(Arc::new(guard));
+        }
+
+        Ok(electrum)
+    }
+
+    /// Plain http only, `https://` addresses need the `tls` feature.
+    #[cfg(not(feature = "tls"))]
+    fn wrap_connector(&self, connector: HttpConnector, address: &Uri) -> Result<Connector> {
+        if address.scheme_str() == Some("https") {
+            return Err(ElectrumRpcError::InvalidParams(
+                "https addresses require the `tls` feature".to_string(),
+            ));
+        }
+
+        Ok(connector)
+    }
+
+    /// TLS for `https://` addresses, verified against the webpki root certificates
+    /// or the pinned certificate, and plain http otherwise. With a pinned
+    /// certificate only `https://` addresses are allowed.
+    #[cfg(feature = "tls")]
+    fn wrap_connector(&self, mut connector: HttpConnector, address: &Uri) -> Result<Connector> {
+        if self.pinned_cert.is_some() && address.scheme_str() != Some("https") {
+            return Err(ElectrumRpcError::InvalidParams(
+                "a pinned certificate requires an https address".to_string(),
+            ));
+        }
+
+        connector.enforce_http(false);
+
+        let builder = hyper_rustls::HttpsConnectorBuilder::new();
+        let builder = match self.pinned_cert {
+            Some(fingerprint) => builder.with_tls_config(
+                rustls::ClientConfig::builder()
+                    .with_safe_defaults()
+                    .with_custom_certificate_verifier(Arc::new(PinnedCertVerifier::new(
+                        fingerprint,
+                    )))
+                    .with_no_client_auth(),
+            ),
+            None => builder.with_webpki_roots(),
+        };
+        let builder = match self.pinned_cert {
+            Some(_) => builder.https_only(),
+            None => builder.https_or_http(),
+        };
+
+        Ok(builder
+            .enable_http1()
+            .enable_http2()
+            .wrap_connector(connector))
+    }
+}
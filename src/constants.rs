@@ -1,3 +1,19 @@
-use std::env;
+use std::time::Duration;
+
 // 12 hours expiration value in secs
 pub const ELECTRUM_DEFAULT_EXPIRATION: u64 = 12 * 60 * 60;
+
+// Balance lookups in flight at once when fetching balances of all addresses
+pub const ADDRESS_BALANCE_CONCURRENCY: usize = 8;
+
+// Longest wait before retrying a call, whatever the `Retry-After` of a rate-limited reply says
+pub const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+// 30 secs between confirmation checks of `pay_and_confirm`
+pub const PAY_AND_CONFIRM_POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+// Confirmation targets of the fee slider in `eta` mode, a fee level of 1 means next block
+pub const FEE_ETA_TARGETS: [u32; 4] = [25, 10, 5, 2];
+
+// Bytes of data an OP_RETURN output may carry and still be relayed by default
+pub const MAX_OP_RETURN_DATA: usize = 80;
@@ -1,14 +1,106 @@
-use std::fmt;
 use std::error;
+use std::fmt;
+use std::io;
+
 pub use hyper::http::uri::InvalidUri;
 
+use crate::btc::Network;
+
 pub type Result<T> = std::result::Result<T, ElectrumRpcError>;
 
+/// Known failure conditions of Electrum operations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ElectrumErrorCode {
+    WalletNotLoaded,
+    AlreadyConfirmed,
+    DustOutput,
+    TransactionNotAdded,
+}
+
+impl ElectrumErrorCode {
+    /// Recognize a known condition from the message of a daemon error.
+    pub fn from_message(message: &str) -> Option<Self> {
+        let message = message.to_lowercase();
+
+        if message.contains("already in block chain") {
+            Some(Self::AlreadyConfirmed)
+        } else {
+            None
+        }
+    }
+}
+
+/// Step of `Electrum::pay_and_confirm` that failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaymentStage {
+    /// Creating the transaction, nothing was sent.
+    Create,
+    /// Broadcasting the transaction, it may or may not have reached the network.
+    Broadcast,
+    /// Waiting for confirmations of the broadcast transaction.
+    Confirm,
+}
+
+impl fmt::Display for PaymentStage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Create => write!(f, "creating the transaction"),
+            Self::Broadcast => write!(f, "broadcasting the transaction"),
+            Self::Confirm => write!(f, "waiting for confirmations"),
+        }
+    }
+}
+
+impl fmt::Display for ElectrumErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::WalletNotLoaded => write!(f, "no wallet is loaded in the daemon"),
+            Self::AlreadyConfirmed => write!(f, "the transaction is already in the block chain"),
+            Self::DustOutput => write!(f, "the output amount is below the dust threshold"),
+            Self::TransactionNotAdded => write!(f, "the wallet didn't add the transaction"),
+        }
+    }
+}
+
 pub enum ElectrumRpcError {
     AddressError(InvalidUri),
     HyperHttpError(hyper::http::Error),
     HyperHttpStreamError(hyper::Error),
+    DaemonUnreachable(hyper::Error),
     JsonError(serde_json::Error),
+    ConnectionPoolBusy,
+    RequestTooLarge,
+    Unauthorized,
+    RateLimited,
+    InvalidTransaction(String),
+    InvalidInvoice(String),
+    InvalidTxid(String),
+    InvalidAddress(String),
+    NetworkMismatch {
+        address: String,
+        network: Network,
+    },
+    Timeout,
+    Cancelled,
+    InvalidParams(String),
+    ElectrumError(ElectrumErrorCode),
+    RpcError {
+        code: i64,
+        message: String,
+        id: serde_json::Value,
+    },
+    NotInMempool(String),
+    NoFeeEstimate,
+    MalformedResponse(String),
+    IdMismatch {
+        expected: serde_json::Value,
+        got: serde_json::Value,
+    },
+    PaymentFailed {
+        stage: PaymentStage,
+        txid: Option<String>,
+        source: Box<ElectrumRpcError>,
+    },
 }
 
 impl fmt::Display for ElectrumRpcError {
@@ -16,8 +108,63 @@ impl fmt::Display for ElectrumRpcError {
         match self {
             Self::AddressError(e) => write!(f, "the provided address couldn't parsed: {}", e),
             Self::HyperHttpError(e) => write!(f, "while calling method was occurred error: {}", e),
-            Self::HyperHttpStreamError(e) => write!(f, "while sending request was occurred error: {}", e),
+            Self::HyperHttpStreamError(e) => {
+                write!(f, "while sending request was occurred error: {}", e)
+            }
+            Self::DaemonUnreachable(e) => write!(
+                f,
+                "the daemon refused connection, is electrum daemon running?: {}",
+                e
+            ),
             Self::JsonError(e) => write!(f, "while working with json was occurred error: {}", e),
+            Self::ConnectionPoolBusy => {
+                write!(f, "no free connection to the daemon was available in time")
+            }
+            Self::RequestTooLarge => {
+                write!(f, "the request body exceeds the size the server accepts")
+            }
+            Self::Unauthorized => write!(f, "the daemon rejected the login or password"),
+            Self::RateLimited => write!(f, "the call was rate limited (429) and not retried again"),
+            Self::InvalidTransaction(e) => write!(f, "the transaction couldn't be parsed: {}", e),
+            Self::InvalidInvoice(e) => write!(f, "the invoice couldn't be decoded: {}", e),
+            Self::InvalidAddress(address) => write!(f, "{:?} is not a bitcoin address", address),
+            Self::NetworkMismatch { address, network } => {
+                write!(f, "the address {} doesn't belong to {}", address, network)
+            }
+            Self::InvalidTxid(txid) => write!(f, "{:?} is not a txid of 64 hex characters", txid),
+            Self::Timeout => write!(f, "the deadline has elapsed"),
+            Self::Cancelled => write!(f, "the operation was cancelled"),
+            Self::InvalidParams(e) => write!(f, "invalid params: {}", e),
+            Self::ElectrumError(code) => write!(f, "electrum error: {}", code),
+            Self::RpcError { code, message, id } => write!(
+                f,
+                "the daemon replied to request {} with error {}: {}",
+                id, code, message
+            ),
+            Self::NotInMempool(txid) => write!(
+                f,
+                "the transaction {} was broadcast but isn't known to the daemon",
+                txid
+            ),
+            Self::NoFeeEstimate => {
+                write!(f, "the daemon has no fee estimate for the requested target")
+            }
+            Self::MalformedResponse(e) => write!(f, "the daemon's response isn't json-rpc: {}", e),
+            Self::IdMismatch { expected, got } => write!(
+                f,
+                "the response id {} doesn't match the request id {}",
+                got, expected
+            ),
+            Self::PaymentFailed {
+                stage,
+                txid: Some(txid),
+                source,
+            } => write!(f, "payment {} failed while {}: {}", txid, stage, source),
+            Self::PaymentFailed {
+                stage,
+                txid: None,
+                source,
+            } => write!(f, "payment failed while {}: {}", stage, source),
         }
     }
 }
@@ -34,7 +181,27 @@ impl error::Error for ElectrumRpcError {
             Self::AddressError(ref e) => Some(e),
             Self::HyperHttpError(ref e) => Some(e),
             Self::HyperHttpStreamError(ref e) => Some(e),
+            Self::DaemonUnreachable(ref e) => Some(e),
             Self::JsonError(ref e) => Some(e),
+            Self::ConnectionPoolBusy => None,
+            Self::RequestTooLarge => None,
+            Self::Unauthorized => None,
+            Self::RateLimited => None,
+            Self::InvalidTransaction(_) => None,
+            Self::InvalidInvoice(_) => None,
+            Self::InvalidTxid(_) => None,
+            Self::InvalidAddress(_) => None,
+            Self::NetworkMismatch { .. } => None,
+            Self::Timeout => None,
+            Self::Cancelled => None,
+            Self::InvalidParams(_) => None,
+            Self::ElectrumError(_) => None,
+            Self::RpcError { .. } => None,
+            Self::NotInMempool(_) => None,
+            Self::NoFeeEstimate => None,
+            Self::MalformedResponse(_) => None,
+            Self::IdMismatch { .. } => None,
+            Self::PaymentFailed { source, .. } => Some(source.as_ref()),
         }
     }
 }
@@ -51,9 +218,25 @@ impl From<hyper::http::Error> for ElectrumRpcError {
     }
 }
 
+fn is_connection_refused(err: &hyper::Error) -> bool {
+    let mut source = error::Error::source(err);
+    while let Some(err) = source {
+        if let Some(io_err) = err.downcast_ref::<io::Error>() {
+            return io_err.kind() == io::ErrorKind::ConnectionRefused;
+        }
+        source = err.source();
+    }
+
+    false
+}
+
 impl From<hyper::Error> for ElectrumRpcError {
     fn from(err: hyper::Error) -> Self {
-        Self::HyperHttpStreamError(err)
+        if err.is_connect() && is_connection_refused(&err) {
+            Self::DaemonUnreachable(err)
+        } else {
+            Self::HyperHttpStreamError(err)
+        }
     }
 }
 
@@ -61,4 +244,4 @@ impl From<serde_json::Error> for ElectrumRpcError {
     fn from(err: serde_json::Error) -> Self {
         Self::JsonError(err)
     }
-}
\ No newline at end of file
+}
@@ -1,36 +1,442 @@
 pub mod tests {
-    use crate::Electrum;
+    use std::convert::Infallible;
     use std::env;
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use hyper::header::HeaderMap;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{body, Body, Request, Response, Server, StatusCode};
     use lazy_static::lazy_static;
+    use serde_json::{json, Value};
+
+    use crate::Electrum;
 
-    lazy_static!(
+    lazy_static! {
         pub static ref ADDR: String = if let Ok(var) = env::var("ELECTRUM_DAEMON_ADDRESS") {
-                var
-            } else {
-                "http://127.0.0.1:7000".to_string()
-            };
+            var
+        } else {
+            "http://127.0.0.1:7000".to_string()
+        };
+        pub static ref LOGIN: String = if let Ok(var) = env::var("ELECTRUM_USER") {
+            var
+        } else {
+            "test".to_string()
+        };
+        pub static ref PASSWORD: String = if let Ok(var) = env::var("ELECTRUM_PASSWORD") {
+            var
+        } else {
+            "test".to_string()
+        };
+    }
 
+    pub fn get_electrum_rpc() -> Electrum {
+        Electrum::new(LOGIN.clone(), PASSWORD.clone(), ADDR.clone()).unwrap()
+    }
 
-        pub static ref LOGIN: String = if let Ok(var) = env::var("ELECTRUM_USER") {
-                var
-            } else {
-                "test".to_string()
-            };
+    /// Canned reply of `MockDaemon`.
+    pub struct MockReply {
+        status: StatusCode,
+        content: MockContent,
+        delay: Option<Duration>,
+        body_delay: Option<Duration>,
+        headers: Vec<(&'static str, String)>,
+    }
 
-        pub static ref PASSWORD: String = if let Ok(var) = env::var("ELECTRUM_PASSWORD") {
-                var
-            } else {
-                "test".to_string()
-            };
-    );
+    enum MockContent {
+        Result(Value),
+        Error(i64, String),
+        Raw(String),
+        Verbatim(String),
+    }
 
+    impl MockReply {
+        /// Reply with a JSON-RPC envelope carrying `result` and the id of the request.
+        pub fn result(result: Value) -> Self {
+            Self::new(MockContent::Result(result))
+        }
 
+        /// Reply with a JSON-RPC error object and the id of the request.
+        pub fn error(code: i64, message: &str) -> Self {
+            Self::new(MockContent::Error(code, message.to_string()))
+        }
 
-    pub fn get_electrum_rpc() -> Electrum {
-        Electrum::new(
-            LOGIN.clone(),
-            PASSWORD.clone(),
-            ADDR.clone(),
-        ).unwrap()
+        /// Reply with `body`, e.g. a fixture, its `id` replaced with the id of
+        /// the request if it is a JSON object. Other bodies are sent as they are.
+        pub fn raw(body: &str) -> Self {
+            Self::new(MockContent::Raw(body.to_string()))
+        }
+
+        /// Reply with the body as is, e.g. to answer with another id.
+        pub fn verbatim(body: &str) -> Self {
+            Self::new(MockContent::Verbatim(body.to_string()))
+        }
+
+        fn new(content: MockContent) -> Self {
+            Self {
+                status: StatusCode::OK,
+                content,
+                delay: None,
+                body_delay: None,
+                headers: Vec::new(),
+            }
+        }
+
+        pub fn status(mut self, status: u16) -> Self {
+            self.status = StatusCode::from_u16(status).unwrap();
+            self
+        }
+
+        pub fn delay(mut self, delay: Duration) -> Self {
+            self.delay = Some(delay);
+            self
+        }
+
+        /// Send the headers right away and the body after `delay`.
+        pub fn body_delay(mut self, delay: Duration) -> Self {
+            self.body_delay = Some(delay);
+            self
+        }
+
+        pub fn header(mut self, name: &'static str, value: &str) -> Self {
+            self.headers.push((name, value.to_string()));
+            self
+        }
+
+        fn body(&self, request: &Value) -> String {
+            match &self.content {
+                MockContent::Result(result) => {
+                    json!({"jsonrpc": "2.0", "result": result, "id": request["id"]}).to_string()
+                }
+                MockContent::Error(code, message) => json!({
+                    "jsonrpc": "2.0",
+                    "error": {"code": code, "message": message},
+                    "id": request["id"]
+                })
+                .to_string(),
+                MockContent::Raw(body) => match serde_json::from_str::<Value>(body) {
+                    Ok(Value::Object(mut envelope)) => {
+                        envelope.insert("id".to_string(), request["id"].clone());
+                        Value::Object(envelope).to_string()
+                    }
+                    _ => body.clone(),
+                },
+                MockContent::Verbatim(body) => body.clone(),
+            }
+        }
+    }
+
+    /// Request received by `MockDaemon`.
+    #[derive(Clone)]
+    pub struct MockRequest {
+        pub path: String,
+        pub headers: HeaderMap,
+        pub body: Value,
+    }
+
+    /// Local http server standing in for Electrum daemon.
+    /// `reply` gets the index of the call and the json body of the request.
+    pub struct MockDaemon {
+        address: String,
+        requests: Arc<Mutex<Vec<MockRequest>>>,
+    }
+
+    impl MockDaemon {
+        pub fn start<F>(reply: F) -> Self
+        where
+            F: Fn(usize, &Value) -> MockReply + Send + Sync + 'static,
+        {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let address = format!("http://{}", listener.local_addr().unwrap());
+            let requests = Arc::new(Mutex::new(Vec::new()));
+            let reply = Arc::new(reply);
+
+            let received = requests.clone();
+            let make_service = make_service_fn(move |_| {
+                let received = received.clone();
+                let reply = reply.clone();
+
+                async move {
+                    Ok::<_, Infallible>(service_fn(move |req: Request<Body>| {
+                        let received = received.clone();
+                        let reply = reply.clone();
+
+                        async move {
+                            let path = req.uri().path().to_string();
+                            let headers = req.headers().clone();
+                            let slice = body::to_bytes(req.into_body()).await.unwrap();
+                            let body: Value = serde_json::from_slice(&slice).unwrap_or(Value::Null);
+
+                            let index = {
+                                let mut received = received.lock().unwrap();
+                                received.push(MockRequest {
+                                    path,
+                                    headers,
+                                    body: body.clone(),
+                                });
+                                received.len() - 1
+                            };
+
+                            let mock = reply(index, &body);
+                            if let Some(delay) = mock.delay {
+                                tokio::time::sleep(delay).await;
+                            }
+
+                            let mut resp = Response::builder().status(mock.status);
+                            for (name, value) in &mock.headers {
+                                resp = resp.header(*name, value.as_str());
+                            }
+
+                            let content = mock.body(&body);
+                            let content = match mock.body_delay {
+                                Some(delay) => {
+                                    let (mut sender, content_body) = Body::channel();
+                                    tokio::spawn(async move {
+                                        tokio::time::sleep(delay).await;
+                                        let _ = sender.send_data(content.into()).await;
+                                    });
+                                    content_body
+                                }
+                                None => Body::from(content),
+                            };
+
+                            Ok::<_, Infallible>(resp.body(content).unwrap())
+                        }
+                    }))
+                }
+            });
+
+            let server = Server::from_tcp(listener).unwrap().serve(make_service);
+            tokio::spawn(server);
+
+            Self { address, requests }
+        }
+
+        pub fn address(&self) -> String {
+            self.address.clone()
+        }
+
+        /// Client pointed at the mock with test credentials.
+        pub fn electrum(&self) -> Electrum {
+            Electrum::new(LOGIN.clone(), PASSWORD.clone(), self.address()).unwrap()
+        }
+
+        pub fn requests(&self) -> Vec<MockRequest> {
+            self.requests.lock().unwrap().clone()
+        }
+
+        pub fn calls(&self) -> usize {
+            self.requests.lock().unwrap().len()
+        }
     }
-}
\ No newline at end of file
+}
+
+/// Canned responses of the daemon, as sent on the wire.
+#[cfg(any(test, feature = "test-util"))]
+pub mod fixtures {
+    /// `getbalance`
+    pub const BALANCE: &str = r#"{"jsonrpc": "2.0", "result": {"confirmed": "0.00151200", "unconfirmed": "0.0002"}, "id": 0}"#;
+
+    /// `getinfo`
+    pub const GET_INFO: &str = r#"{"jsonrpc": "2.0", "result": {
+        "auto_connect": true,
+        "blockchain_height": 1937000,
+        "connected": true,
+        "default_wallet": "/home/electrum/.electrum/testnet/wallets/default_wallet",
+        "fee_per_kb": 1000,
+        "path": "/home/electrum/.electrum/testnet",
+        "protocol_version": "1.4.2",
+        "server": "testnet.aranguren.org",
+        "server_height": 1937002,
+        "spv_nodes": 8,
+        "version": "4.0.9"
+    }, "id": 0}"#;
+
+    /// `broadcast` of a transaction that is already confirmed
+    pub const BROADCAST_ALREADY_CONFIRMED: &str = r#"{"jsonrpc": "2.0", "error": {
+        "code": 1,
+        "message": "The transaction was rejected by network rules.\n\ntransaction already in block chain"
+    }, "id": 0}"#;
+
+    /// `list_requests` of a wallet with onchain and Lightning requests
+    pub const LIST_REQUESTS_LIGHTNING: &str = r#"{"jsonrpc": "2.0", "result": [
+        {
+            "is_lightning": false,
+            "address": "tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn",
+            "amount_BTC": "0.001",
+            "status": 0,
+            "status_str": "Expires in about 12 hours"
+        },
+        {
+            "is_lightning": true,
+            "lightning_invoice": "lntb2500u1pvjluezpp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqdq5xysxxatsyp3k7enxv4jsxqzpuqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqzanpwm",
+            "rhash": "0001020304050607080900010203040506070809000102030405060708090102",
+            "amount_BTC": "0.0025",
+            "status": 3,
+            "status_str": "Paid"
+        },
+        {
+            "is_lightning": true,
+            "lightning_invoice": "lntb1pvjluezpp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqhp5qqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqjjfqp9",
+            "rhash": "0001020304050607080900010203040506070809000102030405060708090102",
+            "status": 1,
+            "status_str": "Expired"
+        }
+    ], "id": 0}"#;
+
+    /// `wallet_info`
+    pub const WALLET_INFO: &str = r#"{"jsonrpc": "2.0", "result": {
+        "wallet_type": "standard",
+        "gap_limit": 20,
+        "seed_available": true,
+        "keystores": [
+            {
+                "type": "bip32",
+                "derivation": "m/84'/1'/0'",
+                "root_fingerprint": "d34db33f",
+                "label": "main"
+            }
+        ],
+        "txin_type": "p2wpkh"
+    }, "id": 0}"#;
+
+    /// `getinfo` of a daemon with Lightning enabled
+    pub const GET_INFO_LIGHTNING: &str = r#"{"jsonrpc": "2.0", "result": {
+        "auto_connect": true,
+        "blockchain_height": 1937000,
+        "connected": true,
+        "default_wallet": "/home/electrum/.electrum/testnet/wallets/default_wallet",
+        "fee_per_kb": 1000,
+        "lightning": true,
+        "path": "/home/electrum/.electrum/testnet",
+        "server": "testnet.aranguren.org",
+        "server_height": 1937002,
+        "spv_nodes": 8,
+        "version": "4.1.5"
+    }, "id": 0}"#;
+
+    /// `listunspent`
+    pub const LIST_UNSPENT: &str = r#"{"jsonrpc": "2.0", "result": [
+        {
+            "address": "tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn",
+            "value": "0.001",
+            "prevout_hash": "eaf3e55e521431bcd18294b8a536a966f5fbdc9f7701cdbaa877cec9e95b8cb5",
+            "prevout_n": 0,
+            "height": 1936900,
+            "coinbase": false
+        },
+        {
+            "address": "tb1q6fezqhhcr8trzy33cpspftk4e7csqqy7zckyz0",
+            "value": "0.000512",
+            "prevout_hash": "3d10b4ad1ad56e4e1e8ad6bc6fe9bb8d4d0c84d7c7b13e2b8c0a4b9f2a6c1d0e",
+            "prevout_n": 1,
+            "height": 0,
+            "coinbase": false
+        }
+    ], "id": 0}"#;
+
+    /// `listaddresses`
+    pub const LIST_ADDRESSES: &str = r#"{"jsonrpc": "2.0", "result": [
+        "tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn",
+        "tb1q6fezqhhcr8trzy33cpspftk4e7csqqy7zckyz0"
+    ], "id": 0}"#;
+
+    /// `listaddresses --balance --labels`
+    pub const LIST_ADDRESSES_DETAILED: &str = r#"{"jsonrpc": "2.0", "result": [
+        ["tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn", "0.001", "'savings'"],
+        ["tb1q6fezqhhcr8trzy33cpspftk4e7csqqy7zckyz0", "0.", "''"]
+    ], "id": 0}"#;
+
+    /// `getaddresshistory`
+    pub const ADDRESS_HISTORY: &str = r#"{"jsonrpc": "2.0", "result": [
+        {"tx_hash": "3d10b4ad1ad56e4e1e8ad6bc6fe9bb8d4d0c84d7c7b13e2b8c0a4b9f2a6c1d0e", "height": 0, "fee": 141},
+        {"tx_hash": "eaf3e55e521431bcd18294b8a536a966f5fbdc9f7701cdbaa877cec9e95b8cb5", "height": 1936900}
+    ], "id": 0}"#;
+
+    /// `onchain_history` of a wallet with an incoming, an outgoing
+    /// and an unconfirmed transaction
+    pub const ONCHAIN_HISTORY: &str = r#"{"jsonrpc": "2.0", "result": {
+        "summary": {
+            "begin_balance": "0.",
+            "end_balance": "0.0007"
+        },
+        "transactions": [
+            {
+                "txid": "eaf3e55e521431bcd18294b8a536a966f5fbdc9f7701cdbaa877cec9e95b8cb5",
+                "height": 1936900,
+                "confirmations": 101,
+                "timestamp": 1613739400,
+                "incoming": true,
+                "bc_value": "0.001",
+                "bc_balance": "0.001",
+                "label": ""
+            },
+            {
+                "txid": "3d10b4ad1ad56e4e1e8ad6bc6fe9bb8d4d0c84d7c7b13e2b8c0a4b9f2a6c1d0e",
+                "height": 1937000,
+                "confirmations": 1,
+                "timestamp": 1613825800,
+                "incoming": false,
+                "bc_value": "-0.0005",
+                "bc_balance": "0.0005",
+                "label": "rent, march"
+            },
+            {
+                "txid": "9a1c2b3d4e5f60718293a4b5c6d7e8f90a1b2c3d4e5f60718293a4b5c6d7e8f9",
+                "height": 0,
+                "confirmations": 0,
+                "timestamp": null,
+                "incoming": true,
+                "bc_value": "0.0002",
+                "bc_balance": "0.0007",
+                "fee_sat": 282,
+                "label": null
+            }
+        ]
+    }, "id": 0}"#;
+
+    /// `signtransaction` of a PSBT with all signatures present
+    pub const SIGN_PSBT_COMPLETE: &str = r#"{"jsonrpc": "2.0", "result": "02000000000101b58c5be9c9ce77a8bacd01779fdcfbf566a936a5b89482d1bc3114525ee5f3ea0000000000fdffffff01a086010000000000160014d27220bef819d631123380c0a7b75cfb5b00009e0247304402203f1e2a5c1b3d8e7f6a9b0c1d2e3f4a5b6c7d8e9f0a1b2c3d4e5f60718293a4b502207a6b5c4d3e2f1a0b9c8d7e6f5a4b3c2d1e0f9a8b7c6d5e4f3a2b1c0d9e8f7a6b0121031b84c5567b126440995d3ed5aaba0565d71e1834604819ff9c17f5e9d5dd078f00000000"}"#;
+
+    /// `signtransaction` of a multisig PSBT still missing a cosigner's signature
+    pub const SIGN_PSBT_INCOMPLETE: &str = r#"{"jsonrpc": "2.0", "result": "cHNidP8BAFICAAAAAbWMW+nJznequM0Bd5/c+/VmqTaluJSC0bwxFFJe5fPqAAAAAAD9////AaCGAQAAAAAAFgAU0nIgvvgZ1jESM4DAp7dc+1sAAJ4AAAAAAAEBH6CGAQAAAAAAFgAUnhF3u80atWAKiqb5KO1sB0R5z5U=", "id": 0}"#;
+
+    /// `getmerkle`
+    pub const MERKLE: &str = r#"{"jsonrpc": "2.0", "result": {
+        "block_height": 1936900,
+        "merkle": [
+            "713d6c7e6ce7bbea708d61162231eaa8ecb31c4c5dd84f81c20409a90069cb24",
+            "03dbaec78d4a52fbaf3c7aa5d3fccd9d8654f323940716ddf5ee2e4bda458fde"
+        ],
+        "pos": 3
+    }, "id": 0}"#;
+
+    /// `deserialize` of a transaction paying 1000 sat with change
+    pub const DESERIALIZE: &str = r#"{"jsonrpc": "2.0", "result": {
+        "version": 2,
+        "locktime": 1970116,
+        "inputs": [
+            {
+                "prevout_hash": "eaf3e55e521431bcd18294b8a536a966f5fbdc9f7701cdbaa877cec9e95b8cb5",
+                "prevout_n": 0,
+                "coinbase": false,
+                "nsequence": 4294967293,
+                "scriptSig": "",
+                "witness": "0247304402203db69d69b3fa76050b6c3276bc21bb834996f2c84c31c17c813beba01079705002202d864669f12db9939ea78a45e4c4a982cca68304fef25f334bd6cbbc9971bc9b012103815054ce939185772574ef569fe31b601d5bad48f48d5edaef194cded838c31a"
+            }
+        ],
+        "outputs": [
+            {
+                "address": "tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn",
+                "scriptpubkey": "00149e08b7dbcd1aad00ec6888a60e56c779f0f2da27",
+                "value_sats": 1000
+            },
+            {
+                "address": "tb1q6fezqhhcr8trzy33cpspftk4e7csqqy7zckyz0",
+                "scriptpubkey": "0014d272035ef819d6311231c06014aed5cfb100009e",
+                "value_sats": 8800
+            }
+        ]
+    }, "id": 0}"#;
+}
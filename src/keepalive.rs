@@ -0,0 +1,53 @@
+//! Pinging the daemon in the background to keep connections warm.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::info;
+use tokio_util::sync::CancellationToken;
+
+use crate::Electrum;
+
+/// Stops the keepalive task once the last client sharing it is dropped.
+pub(crate) struct KeepaliveGuard(CancellationToken);
+
+impl Drop for KeepaliveGuard {
+    fn drop(&mut self) {
+        self.0.cancel();
+    }
+}
+
+/// Call `version` on `client` every `interval`, storing whether the daemon
+/// answered within `interval` in `healthy`. `client` must not hold the
+/// returned guard, or the task would never stop.
+pub(crate) fn spawn(
+    client: Electrum,
+    interval: Duration,
+    healthy: Arc<AtomicBool>,
+) -> KeepaliveGuard {
+    let cancel = CancellationToken::new();
+    let stop = cancel.clone();
+
+    tokio::spawn(async move {
+        let mut ticks = tokio::time::interval(interval);
+        loop {
+            tokio::select! {
+                _ = stop.cancelled() => return,
+                _ = ticks.tick() => {}
+            }
+
+            let ping = tokio::time::timeout(interval, client.version()).await;
+            let alive = matches!(ping, Ok(Ok(_)));
+            if healthy.swap(alive, Ordering::Relaxed) != alive {
+                if alive {
+                    info!("Daemon is responding again");
+                } else {
+                    info!("Daemon stopped responding to keepalive pings");
+                }
+            }
+        }
+    });
+
+    KeepaliveGuard(cancel)
+}
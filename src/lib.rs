@@ -5,26 +5,68 @@ use std::borrow::Borrow;
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::str;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+#[cfg(feature = "metrics")]
+use std::time::Instant;
 
-use base64;
+use futures::stream::{self, Stream, StreamExt, TryStreamExt};
 use hyper::client::HttpConnector;
-use hyper::header::AUTHORIZATION;
-use hyper::{Body, Client, Method, Request, Response, Uri};
+use hyper::header::{AUTHORIZATION, CONTENT_TYPE};
+use hyper::{body, Body, Client, Method, Request, Response, StatusCode, Uri};
 use log::info;
+use rust_decimal::prelude::ToPrimitive;
 use rust_decimal::Decimal;
-use serde::{Deserialize, Serialize};
+use serde::de::{DeserializeOwned, Error as _};
+use serde::{Deserialize, Serialize, Serializer};
 use serde_json::{json, Value};
+use tokio::sync::{Semaphore, SemaphorePermit};
 
-use btc::BtcAddress;
-use constants::ELECTRUM_DEFAULT_EXPIRATION;
-use error::Result;
+use batch::BatchBuilder;
+use btc::{AddressType, Amount, BtcAddress, FeeRate, Network, OwnedBtcAddress, PayAmount, Txid};
+use builder::{ElectrumBuilder, IdGenerator, MerkleCache, PasswordProvider};
+use constants::{
+    ADDRESS_BALANCE_CONCURRENCY, ELECTRUM_DEFAULT_EXPIRATION, FEE_ETA_TARGETS, MAX_OP_RETURN_DATA,
+    PAY_AND_CONFIRM_POLL_INTERVAL,
+};
+use error::{ElectrumErrorCode, ElectrumRpcError, PaymentStage, Result};
+use keepalive::KeepaliveGuard;
+#[cfg(feature = "metrics")]
+use metrics::Metrics;
+use poll::PollConfig;
+#[cfg(feature = "lightning")]
+use responses::LnInvoice;
+use responses::{
+    AddressEntry, Balance, CreatedTransaction, DeserializedTx, GetInfoResponse, HistoryEntry,
+    MerkleProof, SignedPsbt, TxRecord, TxStatus, Utxo, WalletDetails,
+};
+use retry::RetryPolicy;
+use secret::SecretHeader;
+use transaction::PreparedTx;
+use webhook::WebhookServer;
 
+pub use tokio_util::sync::CancellationToken;
+
+pub mod batch;
 pub mod btc;
+pub mod builder;
 mod constants;
 pub mod error;
 pub mod ext;
+mod keepalive;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod poll;
+pub mod responses;
+mod retry;
+mod secret;
+#[cfg(feature = "tls")]
+mod tls;
+pub mod transaction;
+pub mod webhook;
 
-#[derive(Serialize)]
+#[derive(Clone, Serialize)]
 #[serde(rename_all = "lowercase")]
 enum ElectrumMethod {
     Broadcast,
@@ -36,9 +78,29 @@ enum ElectrumMethod {
 
     GetFeeRate,
 
+    #[serde(rename = "getfeehistogram")]
+    GetFeeHistogram,
+
+    GetMerkle,
+    Serialize,
+    Deserialize,
+    IsMine,
+    Freeze,
+    Unfreeze,
+
+    #[serde(rename = "get_tx_status")]
+    GetTxStatus,
+
+    GetTransaction,
+
+    GetRequest,
+    SignMessage,
+    VerifyMessage,
+
     GetBalance,
     GetAddressHistory,
     GetAddressBalance,
+    ListUnspent,
 
     #[serde(rename = "onchain_history")]
     GetOnchainHistory,
@@ -46,6 +108,12 @@ enum ElectrumMethod {
     #[serde(rename = "list_wallets")]
     ListWallets,
 
+    #[serde(rename = "wallet_info")]
+    WalletInfo,
+
+    #[serde(rename = "is_synchronized")]
+    IsSynchronized,
+
     #[serde(rename = "close_wallet")]
     CloseWallet,
 
@@ -58,15 +126,29 @@ enum ElectrumMethod {
     #[serde(rename = "restore")]
     RestoreWallet,
 
+    #[serde(rename = "getmpk")]
+    GetMpk,
+
     ListAddresses,
 
+    #[serde(rename = "createnewaddress")]
+    CreateNewAddress,
+
+    #[serde(rename = "getunusedaddress")]
+    GetUnusedAddress,
+
     #[serde(rename = "list_requests")]
     ListRequests,
 
     Notify,
     Help,
+    Version,
     Empty,
     SignTransaction,
+    AddTransaction,
+
+    #[serde(rename = "setconfig")]
+    SetConfig,
 
     #[serde(rename = "add_request")]
     AddRequest,
@@ -74,7 +156,17 @@ enum ElectrumMethod {
     RemoveRequest,
 }
 
-#[derive(Hash, PartialEq, Eq, Serialize)]
+impl ElectrumMethod {
+    /// Name of the command as sent to the daemon.
+    fn name(&self) -> String {
+        match serde_json::to_value(self) {
+            Ok(Value::String(name)) => name,
+            _ => String::new(),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Serialize)]
 #[serde(rename_all = "lowercase")]
 enum Param {
     Text,
@@ -102,27 +194,84 @@ enum Param {
     Expired,
     Paid,
     Expiration,
+    Txid,
+    Height,
+
+    #[serde(rename = "from_coins")]
+    FromCoins,
+
+    Key,
+    Message,
+    Signature,
+
+    #[serde(rename = "derivation_path")]
+    DerivationPath,
+
+    Passphrase,
+    Value,
+
+    #[serde(rename = "fee_method")]
+    FeeMethod,
+
+    #[serde(rename = "fee_level")]
+    FeeLevel,
+
+    JsonTx,
+
+    Balance,
+    Labels,
+}
+
+/// Params whose values must not appear in logs.
+const SECRET_PARAMS: [Param; 3] = [Param::Password, Param::Passphrase, Param::Text];
+
+impl Param {
+    /// Whether the value must not appear in logs.
+    fn is_secret(&self) -> bool {
+        SECRET_PARAMS.contains(self)
+    }
+
+    /// Whether the param serialized as `name` is secret.
+    fn is_secret_name(name: &str) -> bool {
+        SECRET_PARAMS
+            .iter()
+            .any(|param| serde_json::to_value(param).is_ok_and(|secret| secret == name))
+    }
+}
+
+/// Form of `params` sent with calls that have no params set.
+/// Electrum daemon accepts both for every command, `Object` is the default;
+/// it can be overridden for all methods or per method on the builder.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EmptyParams {
+    /// `"params": {}`
+    Object,
+    /// `"params": []`
+    Array,
 }
 
+/// Version of the protocol sent in the `jsonrpc` field of requests.
+const JSON_RPC_VERSION: &str = "2.0";
+
 struct JsonRpcBodyBuilder {
-    json_rpc: f32,
-    id: u64,
+    json_rpc: &'static str,
+    id: Value,
     method: ElectrumMethod,
-    params: HashMap<Param, Value>,
+    params: Vec<(Param, Value)>,
 }
 
 impl JsonRpcBodyBuilder {
     pub fn new() -> Self {
         Self {
-            json_rpc: 2.0,
-            id: 0,
+            json_rpc: JSON_RPC_VERSION,
+            id: Value::from(0),
             method: ElectrumMethod::Empty,
-            params: HashMap::new(),
+            params: Vec::new(),
         }
     }
 
-    pub fn id(mut self, id: u64) -> Self {
-        self.id = id;
+    pub fn id(mut self, id: impl Into<Value>) -> Self {
+        self.id = id.into();
         self
     }
 
@@ -131,8 +280,13 @@ impl JsonRpcBodyBuilder {
         self
     }
 
+    /// Add `param`, replacing its previous value if already set.
+    /// Params are serialized in the order they were first added.
     pub fn add_param(mut self, param: Param, value: Value) -> Self {
-        self.params.insert(param, value);
+        match self.params.iter_mut().find(|(added, _)| *added == param) {
+            Some((_, added)) => *added = value,
+            None => self.params.push((param, value)),
+        }
         self
     }
 
@@ -146,18 +300,97 @@ impl JsonRpcBodyBuilder {
     }
 }
 
-#[derive(Serialize)]
+#[derive(Clone, Serialize)]
 struct JsonRpcBody {
-    json_rpc: f32,
-    id: u64,
+    #[serde(rename = "jsonrpc")]
+    json_rpc: &'static str,
+    id: Value,
     method: ElectrumMethod,
-    params: HashMap<Param, Value>,
+    #[serde(serialize_with = "serialize_params")]
+    params: Vec<(Param, Value)>,
+}
+
+fn serialize_params<K: Serialize, S: Serializer>(
+    params: &[(K, Value)],
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    serializer.collect_map(params.iter().map(|(param, value)| (param, value)))
+}
+
+/// Request as sent on the wire.
+#[derive(Serialize)]
+struct Envelope<'a, M, K: Serialize> {
+    jsonrpc: &'a str,
+    id: &'a Value,
+    method: M,
+    #[serde(serialize_with = "serialize_params")]
+    params: &'a [(K, Value)],
+}
+
+/// `Envelope` with `"params": []`.
+#[derive(Serialize)]
+struct PositionalEnvelope<'a, M> {
+    jsonrpc: &'a str,
+    id: &'a Value,
+    method: M,
+    params: [Value; 0],
+}
+
+/// Request of a command not covered by `ElectrumMethod`, params as given.
+#[derive(Serialize)]
+struct RawEnvelope<'a> {
+    jsonrpc: &'a str,
+    id: &'a Value,
+    method: &'a str,
+    params: &'a Value,
+}
+
+/// Serialize a request, with empty params in the given form.
+fn encode_envelope<M: Serialize, K: Serialize>(
+    id: &Value,
+    method: M,
+    params: &[(K, Value)],
+    empty_params: EmptyParams,
+) -> Result<String> {
+    if params.is_empty() && empty_params == EmptyParams::Array {
+        return Ok(serde_json::to_string(&PositionalEnvelope {
+            jsonrpc: JSON_RPC_VERSION,
+            id,
+            method,
+            params: [],
+        })?);
+    }
+
+    Ok(serde_json::to_string(&Envelope {
+        jsonrpc: JSON_RPC_VERSION,
+        id,
+        method,
+        params,
+    })?)
 }
 
 impl JsonRpcBody {
+    #[allow(clippy::new_ret_no_self)]
     pub fn new() -> JsonRpcBodyBuilder {
         JsonRpcBodyBuilder::new()
     }
+
+    /// Serialized body, with empty params in the given form.
+    fn to_payload(&self, empty_params: EmptyParams) -> Result<String> {
+        encode_envelope(&self.id, &self.method, &self.params, empty_params)
+    }
+
+    /// Serialized body with secret params masked, for logging.
+    fn redacted(&self) -> Result<String> {
+        let mut json = serde_json::to_value(self)?;
+        for (param, _) in self.params.iter().filter(|(param, _)| param.is_secret()) {
+            if let Value::String(name) = serde_json::to_value(param)? {
+                json["params"][name] = json!("***");
+            }
+        }
+
+        Ok(json.to_string())
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -177,6 +410,399 @@ impl<'a> Invoice<'a> {
     }
 }
 
+/// Inputs with a lower sequence number signal replaceability (BIP 125).
+const RBF_SEQUENCE_LIMIT: u32 = 0xffff_fffe;
+
+/// Status of an unpaid request in Electrum's `getrequest` result.
+const ELECTRUM_REQUEST_UNPAID: u64 = 0;
+
+/// Status of an expired request in Electrum's `getrequest` result.
+const ELECTRUM_REQUEST_EXPIRED: u64 = 1;
+
+/// Status of a paid request in Electrum's `getrequest` result.
+const ELECTRUM_REQUEST_PAID: u64 = 3;
+
+/// Payment request for `add_payment_request`.
+#[derive(Debug, Clone)]
+pub struct PaymentRequest<'a> {
+    amount: Decimal,
+    memo: Option<&'a str>,
+    expiration: u64,
+}
+
+impl<'a> PaymentRequest<'a> {
+    /// Request `amount` BTC, expiring after the default 12 hours.
+    pub fn new(amount: Decimal) -> Self {
+        Self {
+            amount,
+            memo: None,
+            expiration: ELECTRUM_DEFAULT_EXPIRATION,
+        }
+    }
+
+    /// Description shown to the payer.
+    pub fn memo(mut self, memo: &'a str) -> Self {
+        self.memo = Some(memo);
+        self
+    }
+
+    /// Expiration in seconds.
+    pub fn expiration(mut self, expiration: u64) -> Self {
+        self.expiration = expiration;
+        self
+    }
+
+    fn to_body(&self) -> JsonRpcBody {
+        let mut builder = JsonRpcBody::new()
+            .method(ElectrumMethod::AddRequest)
+            .add_param(Param::Amount, btc::btc_value(self.amount));
+
+        if let Some(memo) = self.memo {
+            builder = builder.add_param(Param::Memo, Value::from(memo));
+        }
+
+        builder
+            .add_param(Param::Expiration, Value::from(self.expiration))
+            .build()
+    }
+}
+
+/// Output of a `pay_to_many` transaction, sent as `[address, amount]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Output {
+    pub address: String,
+    /// Amount in BTC.
+    pub amount: Decimal,
+}
+
+impl Serialize for Output {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        (&self.address, self.amount.to_string()).serialize(serializer)
+    }
+}
+
+impl From<(&str, Decimal)> for Output {
+    fn from((address, amount): (&str, Decimal)) -> Self {
+        Self {
+            address: address.to_string(),
+            amount,
+        }
+    }
+}
+
+impl From<(String, Decimal)> for Output {
+    fn from((address, amount): (String, Decimal)) -> Self {
+        Self { address, amount }
+    }
+}
+
+impl<'a> From<(&BtcAddress<'a>, Decimal)> for Output {
+    fn from((address, amount): (&BtcAddress<'a>, Decimal)) -> Self {
+        Self {
+            address: address.address.to_string(),
+            amount,
+        }
+    }
+}
+
+/// Optional settings of `pay_to`.
+#[derive(Debug, Clone, Default)]
+pub struct PayToOptions {
+    fee: Option<Decimal>,
+    fee_rate: Option<Decimal>,
+    from_coins: Option<Vec<String>>,
+}
+
+impl PayToOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Transaction fee in BTC.
+    pub fn fee(mut self, fee: Decimal) -> Self {
+        self.fee = Some(fee);
+        self
+    }
+
+    /// Transaction fee rate in sat/vByte.
+    pub fn fee_rate(mut self, fee_rate: Decimal) -> Self {
+        self.fee_rate = Some(fee_rate);
+        self
+    }
+
+    /// Spend only these wallet coins, given as `txid:n` outpoints.
+    pub fn from_coins(mut self, coins: Vec<String>) -> Self {
+        self.from_coins = Some(coins);
+        self
+    }
+
+    fn to_builder(
+        &self,
+        destination: &BtcAddress,
+        amount: impl Into<PayAmount>,
+    ) -> JsonRpcBodyBuilder {
+        let mut builder = JsonRpcBody::new()
+            .method(ElectrumMethod::PayTo)
+            .add_param(Param::Destination, Value::from(destination))
+            .add_param(Param::Amount, Value::from(amount.into()));
+
+        if let Some(fee) = self.fee {
+            builder = builder.add_param(Param::Fee, Value::from(fee.to_string()));
+        }
+
+        if let Some(fee_rate) = self.fee_rate {
+            builder = builder.add_param(Param::FeeRate, Value::from(fee_rate.to_string()));
+        }
+
+        if let Some(coins) = &self.from_coins {
+            builder = builder.add_param(Param::FromCoins, Value::from(coins.join(",")));
+        }
+
+        builder
+    }
+}
+
+/// Options of `get_transaction_with_options`.
+#[derive(Debug, Clone, Default)]
+pub struct GetTransactionOptions {
+    /// Return the transaction decoded by the daemon instead of raw hex.
+    pub verbose: bool,
+    /// Look the transaction up in this wallet instead of the default one.
+    pub wallet: Option<PathBuf>,
+}
+
+impl GetTransactionOptions {
+    fn to_body(&self, txid: &str) -> JsonRpcBody {
+        let mut builder = JsonRpcBody::new()
+            .method(ElectrumMethod::GetTransaction)
+            .add_param(Param::Txid, Value::from(txid));
+
+        if let Some(wallet) = &self.wallet {
+            builder = builder.add_param(Param::WalletPath, Value::from(wallet.to_string_lossy()));
+        }
+
+        builder.build()
+    }
+}
+
+/// Options of `list_addresses_typed`.
+#[derive(Debug, Clone, Default)]
+pub struct ListAddressesOptions {
+    /// Report the balance of each address.
+    pub balance: bool,
+    /// Report the label of each address.
+    pub labels: bool,
+}
+
+impl ListAddressesOptions {
+    fn to_body(&self) -> JsonRpcBody {
+        let mut builder = JsonRpcBody::new().method(ElectrumMethod::ListAddresses);
+
+        if self.balance {
+            builder = builder.add_param(Param::Balance, Value::from(true));
+        }
+        if self.labels {
+            builder = builder.add_param(Param::Labels, Value::from(true));
+        }
+
+        builder.build()
+    }
+}
+
+/// Fee level of the `eta` fee slider estimating for `target` blocks.
+fn eta_fee_level(target: u32) -> f64 {
+    let position = FEE_ETA_TARGETS
+        .iter()
+        .position(|&blocks| blocks <= target)
+        .unwrap_or(FEE_ETA_TARGETS.len());
+
+    position as f64 / FEE_ETA_TARGETS.len() as f64
+}
+
+/// `payto` of a zero-value OP_RETURN output carrying `data_hex`.
+fn pay_to_data_body(data_hex: &str, fee: Option<Decimal>) -> Result<JsonRpcBodyBuilder> {
+    let data = transaction::decode_hex(data_hex)
+        .map_err(|_| ElectrumRpcError::InvalidParams(format!("{:?} is not hex data", data_hex)))?;
+    if data.len() > MAX_OP_RETURN_DATA {
+        return Err(ElectrumRpcError::InvalidParams(format!(
+            "{} bytes of OP_RETURN data exceed the {} bytes relayed by default",
+            data.len(),
+            MAX_OP_RETURN_DATA
+        )));
+    }
+
+    let mut builder = JsonRpcBody::new()
+        .method(ElectrumMethod::PayTo)
+        .add_param(
+            Param::Destination,
+            Value::from(format!("script(OP_RETURN {})", data_hex.to_lowercase())),
+        )
+        .add_param(Param::Amount, Value::from("0"));
+
+    if let Some(fee) = fee {
+        builder = builder.add_param(Param::Fee, Value::from(fee.to_string()));
+    }
+
+    Ok(builder)
+}
+
+fn set_config_body(key: &str, value: Value) -> JsonRpcBody {
+    JsonRpcBody::new()
+        .method(ElectrumMethod::SetConfig)
+        .add_param(Param::Key, Value::from(key))
+        .add_param(Param::Value, value)
+        .build()
+}
+
+fn restore_from_descriptor_body(descriptor: &str) -> Result<JsonRpcBody> {
+    btc::validate_descriptor(descriptor)?;
+
+    Ok(JsonRpcBody::new()
+        .method(ElectrumMethod::RestoreWallet)
+        .add_param(Param::Text, Value::from(descriptor))
+        .build())
+}
+
+/// Fail with `ElectrumRpcError::RateLimited` if `resp` is a `429` reply.
+fn reject_rate_limited(resp: Response<Body>) -> Result<Response<Body>> {
+    match resp.status() {
+        StatusCode::TOO_MANY_REQUESTS => Err(ElectrumRpcError::RateLimited),
+        _ => Ok(resp),
+    }
+}
+
+/// Read the response body and deserialize its JSON-RPC `result` into `T`.
+/// Fails with `ElectrumRpcError::IdMismatch` if the response isn't for request `id`.
+/// Error replies with a null id, as sent when the daemon couldn't read the
+/// request id, are taken as for `id` so their code and message aren't lost.
+/// With `strict`, fields of the result unknown to `T` are an error.
+async fn parse_result<T: DeserializeOwned>(
+    resp: Response<Body>,
+    id: &Value,
+    strict: bool,
+) -> Result<T> {
+    let json: Value = read_json(resp).await?;
+    let unidentified_error = json["error"].is_object() && json["id"].is_null();
+
+    if json["id"] != *id && !unidentified_error {
+        return Err(ElectrumRpcError::IdMismatch {
+            expected: id.clone(),
+            got: json["id"].clone(),
+        });
+    }
+
+    result_from_envelope(json, strict)
+}
+
+/// Fee in satoshis of transaction `txid`, decoded as `tx`, spending `spent`.
+fn fee_of(txid: &str, tx: &DeserializedTx, spent: u64) -> Result<u64> {
+    let sent: u64 = tx.outputs.iter().map(|output| output.value_sats).sum();
+
+    spent.checked_sub(sent).ok_or_else(|| {
+        ElectrumRpcError::MalformedResponse(format!(
+            "outputs of {} are worth more than its inputs",
+            txid
+        ))
+    })
+}
+
+/// Whether `message` of an error reply says the wallet doesn't know the
+/// transaction asked for.
+fn is_unknown_transaction(message: &str) -> bool {
+    let message = message.to_lowercase();
+
+    message.contains("unknown transaction") || message.contains("transaction not in wallet")
+}
+
+/// Read the whole body of `resp`, so a deadline on the call covers it too.
+async fn buffer_body(resp: Response<Body>) -> Result<Response<Body>> {
+    let (parts, body) = resp.into_parts();
+    let bytes = body::to_bytes(body).await?;
+
+    Ok(Response::from_parts(parts, Body::from(bytes)))
+}
+
+/// Fail with the error of an error reply, see `result_from_envelope`.
+/// Other responses are returned as they are, their body buffered.
+async fn reject_rpc_error(resp: Response<Body>) -> Result<Response<Body>> {
+    let (parts, body) = resp.into_parts();
+    let bytes = body::to_bytes(body).await?;
+
+    if let Ok(json) = serde_json::from_slice::<Value>(&bytes) {
+        if json["error"].is_object() {
+            result_from_envelope::<Value>(json, false)?;
+        }
+    }
+
+    Ok(Response::from_parts(parts, Body::from(bytes)))
+}
+
+/// Read the response body as JSON. An HTML page, as served by a misconfigured
+/// reverse proxy in front of the daemon, fails with `ElectrumRpcError::MalformedResponse`.
+async fn read_json<T: DeserializeOwned>(resp: Response<Body>) -> Result<T> {
+    let is_html = resp
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/html"));
+    let status = resp.status();
+    let slice = body::to_bytes(resp.into_body()).await?;
+
+    if is_html || slice.trim_ascii_start().starts_with(b"<") {
+        return Err(ElectrumRpcError::MalformedResponse(format!(
+            "got an HTML page with status {} instead of JSON, \
+             check that the reverse proxy in front of the daemon forwards to it",
+            status
+        )));
+    }
+
+    Ok(serde_json::from_slice(&slice)?)
+}
+
+/// Deserialize the `result` of a JSON-RPC response envelope into `T`.
+/// An `error` object fails with `ElectrumRpcError::ElectrumError` for known
+/// conditions and `ElectrumRpcError::RpcError` otherwise.
+/// With `strict`, fields of the result unknown to `T` are an error.
+fn result_from_envelope<T: DeserializeOwned>(mut json: Value, strict: bool) -> Result<T> {
+    if json["error"].is_object() {
+        let message = json["error"]["message"].as_str().unwrap_or_default();
+        if let Some(code) = ElectrumErrorCode::from_message(message) {
+            return Err(ElectrumRpcError::ElectrumError(code));
+        }
+
+        return Err(ElectrumRpcError::RpcError {
+            code: json["error"]["code"].as_i64().unwrap_or_default(),
+            message: message.to_string(),
+            id: json["id"].take(),
+        });
+    }
+
+    if strict {
+        let mut unknown = None;
+        let result = serde_ignored::deserialize(json["result"].take(), |path| {
+            unknown.get_or_insert_with(|| path.to_string());
+        })?;
+
+        return match unknown {
+            Some(path) => {
+                Err(serde_json::Error::custom(format!("unknown field `{}`", path)).into())
+            }
+            None => Ok(result),
+        };
+    }
+
+    let result = serde_json::from_value(json["result"].take())?;
+
+    Ok(result)
+}
+
+/// Connector of the http client. With the `tls` feature it also speaks
+/// TLS to `https://` daemons.
+#[cfg(not(feature = "tls"))]
+type Connector = HttpConnector;
+#[cfg(feature = "tls")]
+type Connector = hyper_rustls::HttpsConnector<HttpConnector>;
+
 /// Electrum JSON-RPC client.
 ///
 /// Client represents methods for making json-rpc calls to Electrum daemon.
@@ -198,391 +824,4396 @@ impl<'a> Invoice<'a> {
 ///     Ok(())
 /// }
 /// ```
-
+///
+/// Clones are cheap and share the connection pool, the request id counter
+/// and the metrics with the original, so a client can be handed to several
+/// tasks without an `Arc`.
+#[derive(Clone)]
 pub struct Electrum {
-    auth: String,
+    auth: SecretHeader,
     address: Uri,
-    client: Client<HttpConnector>,
+    client: Client<Connector>,
+    connections: Option<Arc<Semaphore>>,
+    pool_wait: Option<Duration>,
+    password_provider: Option<PasswordProvider>,
+    empty_params: EmptyParams,
+    empty_params_by_method: HashMap<String, EmptyParams>,
+    merkle_cache: Option<MerkleCache>,
+    wallet_preflight: bool,
+    retries: Option<RetryPolicy>,
+    max_batch: usize,
+    timeout: Option<Duration>,
+    strict_parsing: bool,
+    id_generator: Option<IdGenerator>,
+    next_id: Arc<AtomicU64>,
+    network: Option<Network>,
+    dust_check: bool,
+    healthy: Arc<AtomicBool>,
+    keepalive: Option<Arc<KeepaliveGuard>>,
+    #[cfg(feature = "metrics")]
+    metrics: Arc<Metrics>,
 }
 
 impl Electrum {
     /// Create new ElectrumRpc instance
     pub fn new(login: String, password: String, address: String) -> Result<Self> {
-        let client = Client::new();
-        let address = address.parse::<Uri>()?;
-        let credentials = base64::encode(format!("{}:{}", login, password));
-        let auth = format!("Basic {}", credentials);
-
-        Ok(Self {
-            auth,
-            address,
-            client,
-        })
+        Self::builder(login, password, address).build()
     }
 
-    async fn call_method(&self, body: &JsonRpcBody) -> Result<Response<Body>> {
-        let payload = serde_json::to_string(body)?;
-        info!("Payload is: {}", payload);
-
-        let req = Request::builder()
-            .method(Method::POST)
-            .header("accept", "application/json")
-            .header(AUTHORIZATION, &self.auth)
-            .uri(&self.address)
-            .body(Body::from(payload))?;
-
-        let resp = self.client.request(req).await?;
+    /// Create new ElectrumRpc instance and check the credentials with a `version`
+    /// call, failing with `ElectrumRpcError::Unauthorized` if the daemon rejects them.
+    pub async fn new_verified(login: String, password: String, address: String) -> Result<Self> {
+        let client = Self::new(login, password, address)?;
+        client.version().await?;
 
-        Ok(resp)
+        Ok(client)
     }
 
-    /// List all available JSON-RPC calls
-    pub async fn get_help(&self) -> Result<Response<Body>> {
-        self.call_method(
+    /// Version of the daemon.
+    async fn version(&self) -> Result<String> {
+        self.call(
             JsonRpcBody::new()
-                .id(0)
-                .method(ElectrumMethod::Help)
+                .method(ElectrumMethod::Version)
                 .build()
                 .borrow(),
         )
         .await
     }
 
-    /// Fetch the blockchain network info
-    pub async fn get_info(&self) -> Result<Response<Body>> {
-        self.call_method(
-            JsonRpcBody::new()
-                .method(ElectrumMethod::GetInfo)
-                .build()
-                .borrow(),
-        )
-        .await
+    /// Whether the daemon answered the last keepalive ping, see
+    /// `ElectrumBuilder::with_keepalive`. Always `true` without keepalive.
+    pub fn is_healthy(&self) -> bool {
+        self.healthy.load(Ordering::Relaxed)
     }
 
-    /// Return the balance of your wallet.
-    pub async fn get_balance(&self) -> Result<Response<Body>> {
-        self.call_method(
-            JsonRpcBody::new()
-                .method(ElectrumMethod::GetBalance)
-                .build()
-                .borrow(),
-        )
-        .await
+    /// Create builder for configuring ElectrumRpc instance
+    pub fn builder(login: String, password: String, address: String) -> ElectrumBuilder {
+        ElectrumBuilder::new(login, password, address)
     }
 
-    /// Return the transaction history of any address.
-    /// Note: This is a walletless server query, results are not checked by SPV.
-    pub async fn get_address_history<'a>(
-        &self,
-        address: &BtcAddress<'a>,
-    ) -> Result<Response<Body>> {
-        self.call_method(
-            JsonRpcBody::new()
-                .method(ElectrumMethod::GetAddressHistory)
-                .add_param(Param::BtcAddress, Value::from(address))
-                .build()
-                .borrow(),
-        )
-        .await
-    }
+    async fn acquire_connection(&self) -> Result<Option<SemaphorePermit<'_>>> {
+        let connections = match &self.connections {
+            Some(connections) => connections,
+            None => return Ok(None),
+        };
 
-    /// Return the balance of any address.
-    /// Note: This is a walletless server query, results are not checked by SPV.
-    pub async fn get_address_balance<'a>(
-        &self,
-        address: &BtcAddress<'a>,
-    ) -> Result<Response<Body>> {
-        self.call_method(
-            JsonRpcBody::new()
-                .method(ElectrumMethod::GetAddressBalance)
-                .add_param(Param::BtcAddress, Value::from(address))
-                .build()
-                .borrow(),
-        )
-        .await
+        let permit = match self.pool_wait {
+            Some(wait) => tokio::time::timeout(wait, connections.acquire())
+                .await
+                .map_err(|_| ElectrumRpcError::ConnectionPoolBusy)?,
+            None => connections.acquire().await,
+        };
+
+        Ok(Some(permit.expect("connection semaphore is never closed")))
     }
 
-    /// List wallets opened in daemon
-    pub async fn list_wallets(&self) -> Result<Response<Body>> {
-        self.call_method(
-            JsonRpcBody::new()
-                .method(ElectrumMethod::ListWallets)
-                .build()
-                .borrow(),
-        )
-        .await
+    /// Counters of the calls made by this client.
+    #[cfg(feature = "metrics")]
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
     }
 
-    /// Open wallet in daemon
-    pub async fn load_wallet(
+    /// Client metrics in Prometheus text exposition format.
+    #[cfg(feature = "metrics")]
+    pub fn metrics_prometheus(&self) -> String {
+        self.metrics.to_prometheus()
+    }
+
+    async fn call_method(&self, body: &JsonRpcBody) -> Result<Response<Body>> {
+        self.post_body(&self.with_next_id(body)).await
+    }
+
+    /// Id the next request will get, unless ids come from a generator.
+    pub fn next_id(&self) -> u64 {
+        self.next_id.load(Ordering::Relaxed)
+    }
+
+    async fn post_body(&self, body: &JsonRpcBody) -> Result<Response<Body>> {
+        let payload = body.to_payload(self.empty_params_for(&body.method.name()))?;
+        info!("Payload is: {}", body.redacted()?);
+
+        self.post(payload).await
+    }
+
+    /// Id of a new request: from the configured generator, if any,
+    /// otherwise the next value of the incrementing counter.
+    fn take_id(&self) -> Value {
+        match &self.id_generator {
+            Some(generate) => generate(),
+            None => Value::from(self.next_id.fetch_add(1, Ordering::Relaxed)),
+        }
+    }
+
+    /// `body` with the id of a new request.
+    fn with_next_id(&self, body: &JsonRpcBody) -> JsonRpcBody {
+        JsonRpcBody {
+            id: self.take_id(),
+            ..body.clone()
+        }
+    }
+
+    /// Start a json-rpc batch of several calls sent in one request.
+    pub fn batch(&self) -> BatchBuilder<'_> {
+        BatchBuilder::new(self)
+    }
+
+    /// Send `bodies` as json-rpc batches of at most `max_batch` calls each.
+    /// Returns the response envelopes in the order of `bodies`.
+    async fn call_many(&self, bodies: Vec<JsonRpcBodyBuilder>) -> Result<Vec<Value>> {
+        let bodies: Vec<JsonRpcBody> = bodies
+            .into_iter()
+            .map(|body| body.id(self.take_id()).build())
+            .collect();
+
+        let mut envelopes = Vec::with_capacity(bodies.len());
+        for chunk in bodies.chunks(self.max_batch) {
+            let mut payloads = Vec::with_capacity(chunk.len());
+            let mut redacted = Vec::with_capacity(chunk.len());
+            for body in chunk {
+                payloads.push(body.to_payload(self.empty_params_for(&body.method.name()))?);
+                redacted.push(body.redacted()?);
+            }
+            info!("Batch payload is: [{}]", redacted.join(","));
+
+            let resp = self.post(format!("[{}]", payloads.join(","))).await?;
+            let mut replies: Vec<Value> = read_json(resp).await?;
+
+            for body in chunk {
+                let reply = replies
+                    .iter()
+                    .position(|reply| reply["id"] == body.id)
+                    .ok_or_else(|| ElectrumRpcError::IdMismatch {
+                        expected: body.id.clone(),
+                        got: Value::Null,
+                    })?;
+                envelopes.push(replies.swap_remove(reply));
+            }
+        }
+
+        Ok(envelopes)
+    }
+
+    async fn post(&self, payload: String) -> Result<Response<Body>> {
+        #[cfg(feature = "metrics")]
+        let started = Instant::now();
+
+        // The connection stays taken until the whole body is read.
+        let exchange = async {
+            let _permit = self.acquire_connection().await?;
+            buffer_body(self.send_with_retries(&payload).await?).await
+        };
+        let resp = match self.timeout {
+            Some(timeout) => tokio::time::timeout(timeout, exchange)
+                .await
+                .unwrap_or(Err(ElectrumRpcError::Timeout)),
+            None => exchange.await,
+        };
+
+        #[cfg(feature = "metrics")]
+        let resp = match resp {
+            Ok(resp) => self.count_received(resp).await,
+            Err(e) => Err(e),
+        };
+
+        #[cfg(feature = "metrics")]
+        self.metrics.record(started.elapsed(), resp.is_ok());
+
+        resp
+    }
+
+    /// Send `payload`, retrying `429 Too Many Requests` replies and failures
+    /// to connect per the retry policy. A `429` left after the retries fails
+    /// with `ElectrumRpcError::RateLimited`. Errors once connected aren't
+    /// retried: the daemon may have run the call, e.g. a broadcast, already.
+    async fn send_with_retries(&self, payload: &str) -> Result<Response<Body>> {
+        let policy = match self.retries {
+            Some(policy) => policy,
+            None => return self.send(payload).await.and_then(reject_rate_limited),
+        };
+
+        let mut attempt = 0;
+        loop {
+            let resp = match self.send(payload).await {
+                Err(ElectrumRpcError::HyperHttpStreamError(e))
+                | Err(ElectrumRpcError::DaemonUnreachable(e))
+                    if e.is_connect() && attempt < policy.max =>
+                {
+                    let delay = policy.connection_delay(attempt);
+                    attempt += 1;
+                    info!("Connection failed: {}, retry {} in {:?}", e, attempt, delay);
+                    tokio::time::sleep(delay).await;
+                    continue;
+                }
+                resp => resp?,
+            };
+            if resp.status() != StatusCode::TOO_MANY_REQUESTS || attempt >= policy.max {
+                return reject_rate_limited(resp);
+            }
+
+            let delay = policy.delay(attempt, &resp);
+            attempt += 1;
+            info!("Rate limited, retry {} in {:?}", attempt, delay);
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    async fn send(&self, payload: &str) -> Result<Response<Body>> {
+        #[cfg(feature = "metrics")]
+        self.metrics.record_sent(payload.len());
+
+        let req = Request::builder()
+            .method(Method::POST)
+            .header("accept", "application/json")
+            .header(AUTHORIZATION, self.auth.header())
+            .uri(&self.address)
+            .body(Body::from(payload.to_string()))?;
+
+        let resp = self.client.request(req).await?;
+
+        if resp.status() == StatusCode::PAYLOAD_TOO_LARGE {
+            return Err(ElectrumRpcError::RequestTooLarge);
+        }
+
+        if resp.status() == StatusCode::UNAUTHORIZED {
+            return Err(ElectrumRpcError::Unauthorized);
+        }
+
+        Ok(resp)
+    }
+
+    /// Buffer the response body to count its size.
+    #[cfg(feature = "metrics")]
+    async fn count_received(&self, resp: Response<Body>) -> Result<Response<Body>> {
+        let (parts, body) = resp.into_parts();
+        let bytes = body::to_bytes(body).await?;
+        self.metrics.record_received(bytes.len());
+
+        Ok(Response::from_parts(parts, Body::from(bytes)))
+    }
+
+    /// Check `address` belongs to the configured network, if any.
+    fn check_network(&self, address: &str) -> Result<()> {
+        match self.network {
+            Some(network) => BtcAddress::parse_for_network(address, network).map(|_| ()),
+            None => Ok(()),
+        }
+    }
+
+    /// Check an output of `amount` BTC to `address` isn't dust, if enabled.
+    fn check_dust(&self, address: &str, amount: Decimal) -> Result<()> {
+        if self.dust_check && btc::is_dust(amount, AddressType::of(address)?) {
+            return Err(ElectrumRpcError::ElectrumError(
+                ElectrumErrorCode::DustOutput,
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Form of empty params for `method`: its override if set on the
+    /// builder, otherwise the one for all methods.
+    fn empty_params_for(&self, method: &str) -> EmptyParams {
+        self.empty_params_by_method
+            .get(method)
+            .copied()
+            .unwrap_or(self.empty_params)
+    }
+
+    /// Add the wallet password from the configured provider, if any.
+    /// Used only for the calls spending from the wallet.
+    fn with_wallet_password(&self, builder: JsonRpcBodyBuilder) -> JsonRpcBodyBuilder {
+        match &self.password_provider {
+            Some(provider) => builder.add_param(Param::Password, Value::from(provider())),
+            None => builder,
+        }
+    }
+
+    async fn call<T: DeserializeOwned>(&self, body: &JsonRpcBody) -> Result<T> {
+        let body = self.with_next_id(body);
+        let resp = self.post_body(&body).await?;
+        parse_result(resp, &body.id, self.strict_parsing).await
+    }
+
+    /// Like `call`, for results read through a private struct picking only
+    /// the fields a helper needs, so never strict.
+    async fn call_partial<T: DeserializeOwned>(&self, body: &JsonRpcBody) -> Result<T> {
+        let body = self.with_next_id(body);
+        let resp = self.post_body(&body).await?;
+        parse_result(resp, &body.id, false).await
+    }
+
+    /// Bytes of the request body this client would send for `method` with
+    /// `params` as request `id`, without sending it. Pass `next_id()` for the
+    /// id the next request gets. Values of secret params (password,
+    /// passphrase, restore text) are masked unless `redact` is off.
+    pub fn debug_request_bytes(
         &self,
-        wallet_path: Option<PathBuf>,
-        password: Option<&str>,
-    ) -> Result<Response<Body>> {
-        let mut builder = JsonRpcBody::new().method(ElectrumMethod::LoadWallet);
+        id: impl Into<Value>,
+        method: &str,
+        params: &[(&str, Value)],
+        redact: bool,
+    ) -> Result<Vec<u8>> {
+        let params: Vec<(&str, Value)> = params
+            .iter()
+            .map(|(name, value)| {
+                if redact && Param::is_secret_name(name) {
+                    (*name, json!("***"))
+                } else {
+                    (*name, value.clone())
+                }
+            })
+            .collect();
 
-        if let Some(path) = &wallet_path {
-            let path = path.to_str().unwrap();
-            builder = builder.add_param(Param::WalletPath, Value::from(path))
+        let empty_params = self.empty_params_for(method);
+        Ok(encode_envelope(&id.into(), method, &params, empty_params)?.into_bytes())
+    }
+
+    /// Call any daemon command by name, e.g. `getservers`, with `params` as a
+    /// json object of named params, a json array of positional ones or null,
+    /// and return its `result`. The general path for commands without a typed
+    /// method on this client; prefer those where they exist.
+    pub async fn call_raw(&self, method: &str, params: Value) -> Result<Value> {
+        let params = match params {
+            Value::Null if self.empty_params_for(method) == EmptyParams::Array => json!([]),
+            Value::Null => json!({}),
+            params @ Value::Object(_) | params @ Value::Array(_) => params,
+            other => {
+                return Err(ElectrumRpcError::InvalidParams(format!(
+                    "params of {} must be an object or an array, got {}",
+                    method, other
+                )))
+            }
         };
 
-        if let Some(password) = password {
-            builder = builder.add_param(Param::Password, Value::from(password))
+        let id = self.take_id();
+        let envelope = RawEnvelope {
+            jsonrpc: JSON_RPC_VERSION,
+            id: &id,
+            method,
+            params: &params,
         };
+        let payload = serde_json::to_string(&envelope)?;
 
-        self.call_method(&builder.build()).await
+        let mut redacted = serde_json::to_value(&envelope)?;
+        if let Value::Object(params) = &mut redacted["params"] {
+            for (name, value) in params.iter_mut() {
+                if Param::is_secret_name(name) {
+                    *value = json!("***");
+                }
+            }
+        }
+        info!("Payload is: {}", redacted);
+
+        let resp = self.post(payload).await?;
+        parse_result(resp, &id, self.strict_parsing).await
     }
 
-    ///Create a new wallet
-    pub async fn create_wallet(&self) -> Result<Response<Body>> {
+    /// List all available JSON-RPC calls
+    pub async fn get_help(&self) -> Result<Response<Body>> {
         self.call_method(
             JsonRpcBody::new()
-                .method(ElectrumMethod::CreateWallet)
+                .id(0)
+                .method(ElectrumMethod::Help)
                 .build()
                 .borrow(),
         )
         .await
     }
 
-    /// List wallet addresses.
-    /// Returns the list of all addresses in your wallet.
-    /// Use optional arguments to filter the results
-    pub async fn list_addresses(&self) -> Result<Response<Body>> {
-        self.call_method(
+    /// Fetch the blockchain network info
+    pub async fn get_info(&self) -> Result<GetInfoResponse> {
+        self.call(
             JsonRpcBody::new()
-                .method(ElectrumMethod::ListAddresses)
+                .method(ElectrumMethod::GetInfo)
                 .build()
                 .borrow(),
         )
         .await
     }
-    /// Watch an address.
-    /// Every time the address changes, a http POST is sent to the URL.
-    /// Call with an `None` URL to stop watching an address.
-    pub async fn notify<'a>(
-        &self,
-        address: &BtcAddress<'a>,
-        url: Option<Uri>,
-    ) -> Result<Response<Body>> {
-        let url = url.unwrap_or(Uri::from_static("")).to_string();
-
-        let builder = JsonRpcBody::new()
-            .method(ElectrumMethod::Notify)
-            .add_param(Param::BtcAddress, Value::from(address))
-            .add_param(Param::Url, Value::from(url));
-
-        self.call_method(&builder.build()).await
-    }
 
-    /// Restore a wallet from `text`. `text` can be a seed phrase, a master
-    /// public key, a master private key, a list of bitcoin addresses
-    /// or bitcoin private keys.
-    pub async fn restore_wallet(&self, text: &str) -> Result<Response<Body>> {
+    /// Fetch the blockchain network info as the raw daemon response.
+    pub async fn get_info_raw(&self) -> Result<Response<Body>> {
         self.call_method(
             JsonRpcBody::new()
-                .method(ElectrumMethod::RestoreWallet)
-                .add_param(Param::Text, Value::from(text))
+                .method(ElectrumMethod::GetInfo)
                 .build()
                 .borrow(),
         )
         .await
     }
 
-    /// Sign a transaction. The wallet keys will be used unless a private key is provided.
-    pub async fn sign_transaction(&self, tx: &str) -> Result<Response<Body>> {
+    /// Return the balance of your wallet.
+    pub async fn get_balance(&self) -> Result<Response<Body>> {
         self.call_method(
             JsonRpcBody::new()
-                .method(ElectrumMethod::SignTransaction)
-                .add_param(Param::Transaction, Value::from(tx))
+                .method(ElectrumMethod::GetBalance)
                 .build()
                 .borrow(),
         )
         .await
     }
 
-    /// Broadcast a transaction to the network.
-    pub async fn broadcast(&self, tx: &str) -> Result<Response<Body>> {
+    /// Return how much can be sent right now.
+    /// Only confirmed funds are counted unless `include_unconfirmed` is set.
+    pub async fn spendable_balance(&self, include_unconfirmed: bool) -> Result<Decimal> {
+        let balance: Balance = self
+            .call(
+                JsonRpcBody::new()
+                    .method(ElectrumMethod::GetBalance)
+                    .build()
+                    .borrow(),
+            )
+            .await?;
+
+        if include_unconfirmed {
+            Ok(balance.confirmed + balance.unconfirmed)
+        } else {
+            Ok(balance.confirmed)
+        }
+    }
+
+    /// Return the transaction history of any address.
+    /// Note: This is a walletless server query, results are not checked by SPV.
+    pub async fn get_address_history<'a>(
+        &self,
+        address: &BtcAddress<'a>,
+    ) -> Result<Response<Body>> {
         self.call_method(
             JsonRpcBody::new()
-                .method(ElectrumMethod::Broadcast)
-                .add_param(Param::Transaction, Value::from(tx))
+                .method(ElectrumMethod::GetAddressHistory)
+                .add_param(Param::BtcAddress, Value::from(address))
                 .build()
                 .borrow(),
         )
         .await
     }
 
-    /// Create a transaction.
-    pub async fn pay_to<'a>(
+    /// Return the transaction history of any address, parsed.
+    /// Electrum doesn't guarantee the order of entries, set `sort_by_height`
+    /// to get confirmed entries by height with unconfirmed ones last.
+    /// Note: This is a walletless server query, results are not checked by SPV.
+    pub async fn get_address_history_typed<'a>(
         &self,
-        destination: &BtcAddress<'a>,
-        amount: Decimal,
-        fee: Option<Decimal>,
-        fee_rate: Option<Decimal>,
-    ) -> Result<Response<Body>> {
-        let mut builder = JsonRpcBody::new()
-            .method(ElectrumMethod::PayTo)
-            .add_param(Param::Destination, Value::from(destination))
-            .add_param(Param::Amount, Value::from(amount.to_string()));
-
-        if let Some(fee) = fee {
-            builder = builder.add_param(Param::Fee, Value::from(fee.to_string()));
-        }
+        address: &BtcAddress<'a>,
+        sort_by_height: bool,
+    ) -> Result<Vec<HistoryEntry>> {
+        let mut history: Vec<HistoryEntry> = self
+            .call(
+                JsonRpcBody::new()
+                    .method(ElectrumMethod::GetAddressHistory)
+                    .add_param(Param::BtcAddress, Value::from(address))
+                    .build()
+                    .borrow(),
+            )
+            .await?;
 
-        if let Some(fee_rate) = fee_rate {
-            builder = builder.add_param(Param::FeeRate, Value::from(fee_rate.to_string()));
+        if sort_by_height {
+            responses::sort_history_by_height(&mut history);
         }
 
-        self.call_method(&builder.build()).await
+        Ok(history)
     }
 
-    /// Create a multi-output transaction.
-    pub async fn pay_to_many(
+    /// Return the balance of any address.
+    /// Note: This is a walletless server query, results are not checked by SPV.
+    pub async fn get_address_balance<'a>(
         &self,
-        fee: Decimal,
-        outputs: Vec<(String, Decimal)>,
+        address: &BtcAddress<'a>,
     ) -> Result<Response<Body>> {
-        let outputs = json!(outputs);
-        let fee = fee.to_string();
         self.call_method(
             JsonRpcBody::new()
-                .method(ElectrumMethod::PayToMany)
-                .add_param(Param::Fee, Value::from(fee))
-                .add_param(Param::Outputs, outputs)
+                .method(ElectrumMethod::GetAddressBalance)
+                .add_param(Param::BtcAddress, Value::from(address))
                 .build()
                 .borrow(),
         )
         .await
     }
 
-    /// Close opened wallet.
-    pub async fn close_wallet(&self) -> Result<Response<Body>> {
-        self.call_method(
+    /// Return the balance of any address, parsed.
+    /// Note: This is a walletless server query, results are not checked by SPV.
+    pub async fn get_address_balance_typed<'a>(&self, address: &BtcAddress<'a>) -> Result<Balance> {
+        self.call(
             JsonRpcBody::new()
-                .method(ElectrumMethod::CloseWallet)
+                .method(ElectrumMethod::GetAddressBalance)
+                .add_param(Param::BtcAddress, Value::from(address))
                 .build()
                 .borrow(),
         )
         .await
     }
 
-    /// Create a payment request, using the first unused address of the wallet.
-    /// The address will be considered as used after this operation.
-    /// If no payment is received, the address will be considered as unused
-    /// if the payment request is deleted from the wallet.
-    pub async fn add_request(
-        &self,
-        amount: Decimal,
-        memo: Option<&str>,
-        expiration: Option<u64>,
-    ) -> Result<Response<Body>> {
-        let amount = amount.to_string();
-
-        let mut builder = JsonRpcBody::new()
-            .method(ElectrumMethod::AddRequest)
-            .add_param(Param::Amount, Value::from(amount.to_string()));
-
-        if let Some(memo) = memo {
-            builder = builder.add_param(Param::Memo, Value::from(memo))
-        };
-
-        let expiration = expiration.unwrap_or(ELECTRUM_DEFAULT_EXPIRATION);
-        builder = builder.add_param(Param::Expiration, Value::from(expiration));
+    /// Return the balances of `addresses` in the same order, fetched with
+    /// json-rpc batches split per `with_max_batch`.
+    /// Note: This is a walletless server query, results are not checked by SPV.
+    pub async fn get_address_balances(&self, addresses: &[BtcAddress<'_>]) -> Result<Vec<Balance>> {
+        let bodies = addresses
+            .iter()
+            .map(|address| {
+                JsonRpcBody::new()
+                    .method(ElectrumMethod::GetAddressBalance)
+                    .add_param(Param::BtcAddress, Value::from(address))
+            })
+            .collect();
 
-        self.call_method(&builder.build()).await
+        self.call_many(bodies)
+            .await?
+            .into_iter()
+            .map(|json| result_from_envelope(json, self.strict_parsing))
+            .collect()
     }
 
-    /// List the payment requests you made.
-    /// You can combine `pending`, `expired` and `paid` flags for filtering.
-    pub async fn list_requests(
-        &self,
-        pending: bool,
-        expired: bool,
-        paid: bool,
-    ) -> Result<Response<Body>> {
-        self.call_method(
+    /// Unspent outputs of the wallet.
+    pub async fn list_unspent(&self) -> Result<Vec<Utxo>> {
+        self.call(
             JsonRpcBody::new()
-                .method(ElectrumMethod::ListRequests)
-                .add_param(Param::Pending, Value::from(pending))
-                .add_param(Param::Expired, Value::from(expired))
-                .add_param(Param::Paid, Value::from(paid))
+                .method(ElectrumMethod::ListUnspent)
                 .build()
                 .borrow(),
         )
         .await
     }
 
-    pub async fn remove_request<'a>(&self, address: &BtcAddress<'a>) -> Result<Response<Body>> {
+    /// Unspent output `prevout_hash:prevout_n` of the wallet, `None` if it is
+    /// spent or not the wallet's, e.g. to check a coin before spending it.
+    pub async fn get_utxo(&self, prevout_hash: &str, prevout_n: u32) -> Result<Option<Utxo>> {
+        Ok(self
+            .list_unspent()
+            .await?
+            .into_iter()
+            .find(|utxo| utxo.prevout_hash == prevout_hash && utxo.prevout_n == prevout_n))
+    }
+
+    /// Total value in BTC of the confirmed unspent outputs of the wallet,
+    /// e.g. to preview what coin selection can spend.
+    pub async fn confirmed_utxo_value(&self) -> Result<Decimal> {
+        let confirmed: Vec<Utxo> = self
+            .list_unspent()
+            .await?
+            .into_iter()
+            .filter(Utxo::is_confirmed)
+            .collect();
+
+        Ok(responses::total_value(&confirmed))
+    }
+
+    /// List wallets opened in daemon
+    pub async fn list_wallets(&self) -> Result<Response<Body>> {
         self.call_method(
             JsonRpcBody::new()
-                .method(ElectrumMethod::RemoveRequest)
-                .add_param(Param::BtcAddress, Value::from(address))
+                .method(ElectrumMethod::ListWallets)
                 .build()
                 .borrow(),
         )
         .await
     }
 
-    /// Return current suggested fee rate (in sat/kvByte),
-    /// according to config settings of electrum.
-    pub async fn get_fee_rate(&self) -> Result<Response<Body>> {
-        self.call_method(
+    /// Type, gap limit and keystores of the loaded wallet.
+    pub async fn get_wallet_info(&self) -> Result<WalletDetails> {
+        self.call(
             JsonRpcBody::new()
-                .method(ElectrumMethod::GetFeeRate)
+                .method(ElectrumMethod::WalletInfo)
                 .build()
                 .borrow(),
         )
         .await
     }
 
-    /// Wallet onchain history.
-    /// Returns the transaction history of your wallet.
-    pub async fn get_onchain_history(&self) -> Result<Response<Body>> {
-        self.call_method(
+    /// Fail with `ElectrumErrorCode::WalletNotLoaded` if preflight is enabled
+    /// and the daemon has no wallet loaded.
+    async fn ensure_wallet_loaded(&self) -> Result<()> {
+        if !self.wallet_preflight {
+            return Ok(());
+        }
+
+        let wallets: Vec<Value> = self
+            .call(
+                JsonRpcBody::new()
+                    .method(ElectrumMethod::ListWallets)
+                    .build()
+                    .borrow(),
+            )
+            .await?;
+
+        if wallets.is_empty() {
+            return Err(ElectrumRpcError::ElectrumError(
+                ElectrumErrorCode::WalletNotLoaded,
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Open wallet in daemon
+    pub async fn load_wallet(
+        &self,
+        wallet_path: Option<PathBuf>,
+        password: Option<&str>,
+    ) -> Result<Response<Body>> {
+        let mut builder = JsonRpcBody::new().method(ElectrumMethod::LoadWallet);
+
+        if let Some(path) = &wallet_path {
+            let path = path.to_str().unwrap();
+            builder = builder.add_param(Param::WalletPath, Value::from(path))
+        };
+
+        if let Some(password) = password {
+            builder = builder.add_param(Param::Password, Value::from(password))
+        };
+
+        self.call_method(&builder.build()).await
+    }
+
+    /// Open wallet `name` from the wallets directory of the daemon.
+    /// The directory is resolved from the data path reported by `getinfo`.
+    pub async fn load_wallet_by_name(
+        &self,
+        name: &str,
+        password: Option<&str>,
+    ) -> Result<Response<Body>> {
+        let wallet_path = self.wallets_dir().await?.join(name);
+        self.load_wallet(Some(wallet_path), password).await
+    }
+
+    /// Wallets directory of the daemon, resolved from the data path reported by `getinfo`.
+    async fn wallets_dir(&self) -> Result<PathBuf> {
+        Ok(self.get_info().await?.path.join("wallets"))
+    }
+
+    /// Master public key of the loaded wallet. The daemon has no offline
+    /// derivation command, so the xpub of a seed is only available after
+    /// restoring it into a wallet file with `restore_wallet`.
+    pub async fn get_mpk(&self) -> Result<String> {
+        self.call(
             JsonRpcBody::new()
-                .method(ElectrumMethod::GetOnchainHistory)
+                .method(ElectrumMethod::GetMpk)
+                .build()
+                .borrow(),
+        )
+        .await
+    }
+
+    ///Create a new wallet
+    pub async fn create_wallet(&self) -> Result<Response<Body>> {
+        self.call_method(
+            JsonRpcBody::new()
+                .method(ElectrumMethod::CreateWallet)
+                .build()
+                .borrow(),
+        )
+        .await
+    }
+
+    /// List wallet addresses.
+    /// Returns the list of all addresses in your wallet.
+    /// Use optional arguments to filter the results
+    pub async fn list_addresses(&self) -> Result<Response<Body>> {
+        self.call_method(
+            JsonRpcBody::new()
+                .method(ElectrumMethod::ListAddresses)
+                .build()
+                .borrow(),
+        )
+        .await
+    }
+
+    /// Wallet addresses as typed entries, with balances and labels as
+    /// asked for in `options`, see `responses::AddressEntry`.
+    pub async fn list_addresses_typed(
+        &self,
+        options: &ListAddressesOptions,
+    ) -> Result<Vec<AddressEntry>> {
+        self.call(&options.to_body()).await
+    }
+
+    /// All wallet addresses with their balances, in the order of `listaddresses`.
+    /// Balances are fetched concurrently, a few at a time.
+    pub async fn addresses_with_balances(&self) -> Result<Vec<(OwnedBtcAddress, Balance)>> {
+        let addresses: Vec<OwnedBtcAddress> = self
+            .call(
+                JsonRpcBody::new()
+                    .method(ElectrumMethod::ListAddresses)
+                    .build()
+                    .borrow(),
+            )
+            .await?;
+
+        stream::iter(addresses)
+            .map(|address| async move {
+                let balance = self
+                    .get_address_balance_typed(&address.as_btc_address())
+                    .await?;
+                Ok((address, balance))
+            })
+            .buffered(ADDRESS_BALANCE_CONCURRENCY)
+            .try_collect()
+            .await
+    }
+
+    /// Create a new receiving address, beyond the gap limit of the wallet.
+    pub async fn create_new_address(&self) -> Result<OwnedBtcAddress> {
+        self.call(
+            JsonRpcBody::new()
+                .method(ElectrumMethod::CreateNewAddress)
+                .build()
+                .borrow(),
+        )
+        .await
+    }
+
+    /// First unused receiving address of the wallet. Unlike `add_request`,
+    /// doesn't reserve the address, so repeated calls return the same one
+    /// until it receives a payment.
+    pub async fn get_unused_address(&self) -> Result<OwnedBtcAddress> {
+        self.call(
+            JsonRpcBody::new()
+                .method(ElectrumMethod::GetUnusedAddress)
+                .build()
+                .borrow(),
+        )
+        .await
+    }
+
+    /// Create `count` distinct new receiving addresses,
+    /// e.g. to pre-generate addresses for a batch of invoices.
+    /// Fails with `ElectrumRpcError::MalformedResponse` if the daemon keeps
+    /// returning addresses it already returned, after `2 * count` calls.
+    pub async fn get_unused_addresses(&self, count: usize) -> Result<Vec<OwnedBtcAddress>> {
+        let max_attempts = count.saturating_mul(2);
+        let mut addresses = Vec::with_capacity(count);
+        for _ in 0..max_attempts {
+            if addresses.len() == count {
+                break;
+            }
+
+            let address = self.create_new_address().await?;
+            if !addresses.contains(&address) {
+                addresses.push(address);
+            }
+        }
+
+        if addresses.len() < count {
+            return Err(ElectrumRpcError::MalformedResponse(format!(
+                "got {} distinct new addresses out of {} in {} calls",
+                addresses.len(),
+                count,
+                max_attempts
+            )));
+        }
+
+        Ok(addresses)
+    }
+
+    /// Watch an address.
+    /// Every time the address changes, a http POST is sent to the URL.
+    /// Call with an `None` URL to stop watching an address.
+    pub async fn notify<'a>(
+        &self,
+        address: &BtcAddress<'a>,
+        url: Option<Uri>,
+    ) -> Result<Response<Body>> {
+        let url = url.unwrap_or(Uri::from_static("")).to_string();
+
+        let builder = JsonRpcBody::new()
+            .method(ElectrumMethod::Notify)
+            .add_param(Param::BtcAddress, Value::from(address))
+            .add_param(Param::Url, Value::from(url));
+
+        self.call_method(&builder.build()).await
+    }
+
+    /// Watch `addresses` with one json-rpc batch per `with_max_batch` calls.
+    /// Returns the result of each registration next to its address, so a few
+    /// rejected addresses don't fail the others.
+    pub async fn notify_many(
+        &self,
+        addresses: &[BtcAddress<'_>],
+        url: Option<Uri>,
+    ) -> Result<Vec<(OwnedBtcAddress, Result<bool>)>> {
+        let url = url.map(|url| url.to_string()).unwrap_or_default();
+
+        let bodies = addresses
+            .iter()
+            .map(|address| {
+                JsonRpcBody::new()
+                    .method(ElectrumMethod::Notify)
+                    .add_param(Param::BtcAddress, Value::from(address))
+                    .add_param(Param::Url, Value::from(url.as_str()))
+            })
+            .collect();
+
+        let envelopes = self.call_many(bodies).await?;
+
+        Ok(addresses
+            .iter()
+            .map(OwnedBtcAddress::from)
+            .zip(
+                envelopes
+                    .into_iter()
+                    .map(|json| result_from_envelope(json, self.strict_parsing)),
+            )
+            .collect())
+    }
+
+    /// Check if address is in wallet.
+    pub async fn is_mine<'a>(&self, address: &BtcAddress<'a>) -> Result<bool> {
+        self.call(
+            JsonRpcBody::new()
+                .method(ElectrumMethod::IsMine)
+                .add_param(Param::BtcAddress, Value::from(address))
+                .build()
+                .borrow(),
+        )
+        .await
+    }
+
+    /// Freeze `address`, excluding its coins from spending.
+    pub async fn freeze<'a>(&self, address: &BtcAddress<'a>) -> Result<bool> {
+        self.call(
+            JsonRpcBody::new()
+                .method(ElectrumMethod::Freeze)
+                .add_param(Param::BtcAddress, Value::from(address))
+                .build()
+                .borrow(),
+        )
+        .await
+    }
+
+    /// Unfreeze `address`, making its coins spendable again.
+    pub async fn unfreeze<'a>(&self, address: &BtcAddress<'a>) -> Result<bool> {
+        self.call(
+            JsonRpcBody::new()
+                .method(ElectrumMethod::Unfreeze)
+                .add_param(Param::BtcAddress, Value::from(address))
+                .build()
+                .borrow(),
+        )
+        .await
+    }
+
+    /// Addresses of the outputs of raw transaction `tx`, decoded by the daemon.
+    /// `None` for outputs without an address, e.g. OP_RETURN.
+    async fn output_addresses(&self, tx: &str) -> Result<Vec<Option<OwnedBtcAddress>>> {
+        #[derive(Deserialize)]
+        struct Output {
+            address: Option<OwnedBtcAddress>,
+        }
+
+        #[derive(Deserialize)]
+        struct Outputs {
+            outputs: Vec<Output>,
+        }
+
+        let decoded: Outputs = self
+            .call_partial(
+                JsonRpcBody::new()
+                    .method(ElectrumMethod::Deserialize)
+                    .add_param(Param::Transaction, Value::from(tx))
+                    .build()
+                    .borrow(),
+            )
+            .await?;
+
+        Ok(decoded
+            .outputs
+            .into_iter()
+            .map(|output| output.address)
+            .collect())
+    }
+
+    /// Watch balance of an address.
+    /// Registers `webhook` url with `notify` and yields the updated balance
+    /// of the address on every notification posted by the daemon.
+    pub async fn watch_balance<'a>(
+        &'a self,
+        address: &BtcAddress<'_>,
+        webhook: WebhookServer,
+    ) -> Result<impl Stream<Item = Result<Balance>> + 'a> {
+        let address = OwnedBtcAddress::from(address);
+        let _registered: bool = self
+            .call(
+                JsonRpcBody::new()
+                    .method(ElectrumMethod::Notify)
+                    .add_param(Param::BtcAddress, Value::from(&address.as_btc_address()))
+                    .add_param(Param::Url, Value::from(webhook.url().to_string()))
+                    .build()
+                    .borrow(),
+            )
+            .await?;
+
+        Ok(stream::unfold(webhook, move |mut webhook| {
+            let address = address.clone();
+            async move {
+                webhook.next_event().await?;
+                let balance = self
+                    .get_address_balance_typed(&address.as_btc_address())
+                    .await;
+
+                Some((balance, webhook))
+            }
+        }))
+    }
+
+    /// Restore a wallet from `text`. `text` can be a seed phrase, a master
+    /// public key, a master private key, a list of bitcoin addresses
+    /// or bitcoin private keys.
+    pub async fn restore_wallet(&self, text: &str) -> Result<Response<Body>> {
+        self.call_method(
+            JsonRpcBody::new()
+                .method(ElectrumMethod::RestoreWallet)
+                .add_param(Param::Text, Value::from(text))
+                .build()
+                .borrow(),
+        )
+        .await
+    }
+
+    /// Restore a wallet from an output script descriptor, e.g.
+    /// `wpkh([d34db33f/84h/0h/0h]xpub.../0/*)`. The descriptor syntax is
+    /// checked before anything is sent; key material is left to the daemon.
+    /// Requires an Electrum version that accepts descriptors in `restore`.
+    pub async fn restore_from_descriptor(&self, descriptor: &str) -> Result<Response<Body>> {
+        self.call_method(&restore_from_descriptor_body(descriptor)?)
+            .await
+    }
+
+    /// Sign a transaction. The wallet keys will be used unless a private key is provided.
+    pub async fn sign_transaction(&self, tx: &str) -> Result<Response<Body>> {
+        let builder = JsonRpcBody::new()
+            .method(ElectrumMethod::SignTransaction)
+            .add_param(Param::Transaction, Value::from(tx));
+
+        self.call_method(&self.with_wallet_password(builder).build())
+            .await
+    }
+
+    /// Add transaction `tx`, e.g. signed offline, to the wallet history
+    /// without broadcasting it, returning its txid. Fails with
+    /// `ElectrumErrorCode::TransactionNotAdded` if the wallet refuses it,
+    /// e.g. as it conflicts with the wallet history.
+    pub async fn add_transaction(&self, tx: &str) -> Result<String> {
+        let added: Value = self
+            .call(
+                JsonRpcBody::new()
+                    .method(ElectrumMethod::AddTransaction)
+                    .add_param(Param::Transaction, Value::from(tx))
+                    .build()
+                    .borrow(),
+            )
+            .await?;
+
+        match added {
+            Value::String(txid) => Ok(txid),
+            Value::Bool(false) => Err(ElectrumRpcError::ElectrumError(
+                ElectrumErrorCode::TransactionNotAdded,
+            )),
+            other => Err(ElectrumRpcError::MalformedResponse(format!(
+                "addtransaction returned {}",
+                other
+            ))),
+        }
+    }
+
+    /// Sign PSBT `psbt` with the wallet keys, telling whether all signatures are
+    /// now present, e.g. for multisig coordinators deciding when to finalize.
+    /// `password` takes precedence over the configured password provider.
+    pub async fn sign_psbt_typed(&self, psbt: &str, password: Option<&str>) -> Result<SignedPsbt> {
+        let mut builder = JsonRpcBody::new()
+            .method(ElectrumMethod::SignTransaction)
+            .add_param(Param::Transaction, Value::from(psbt));
+
+        builder = match password {
+            Some(password) => builder.add_param(Param::Password, Value::from(password)),
+            None => self.with_wallet_password(builder),
+        };
+
+        self.call(&builder.build()).await
+    }
+
+    /// Sign a message with the key of `address`. Returns base64 signature.
+    pub async fn sign_message<'a>(
+        &self,
+        address: &BtcAddress<'a>,
+        message: &str,
+    ) -> Result<String> {
+        self.call(&self.sign_message_body(address, message, None).build())
+            .await
+    }
+
+    /// Sign a message with the key at BIP32 `derivation_path` (e.g. `m/84'/1'/0'/0/3`),
+    /// for multi-account wallets. Returns base64 signature.
+    /// The daemon must support the `derivation_path` param of `signmessage`.
+    pub async fn sign_message_with_derivation<'a>(
+        &self,
+        address: &BtcAddress<'a>,
+        message: &str,
+        derivation_path: &str,
+    ) -> Result<String> {
+        let builder = self.sign_message_body(address, message, Some(derivation_path));
+        self.call(&builder.build()).await
+    }
+
+    /// Check base64 `signature` of `message` made with the key of `address`.
+    pub async fn verify_message<'a>(
+        &self,
+        address: &BtcAddress<'a>,
+        signature: &str,
+        message: &str,
+    ) -> Result<bool> {
+        self.call(
+            JsonRpcBody::new()
+                .method(ElectrumMethod::VerifyMessage)
+                .add_param(Param::BtcAddress, Value::from(address))
+                .add_param(Param::Signature, Value::from(signature))
+                .add_param(Param::Message, Value::from(message))
+                .build()
+                .borrow(),
+        )
+        .await
+    }
+
+    fn sign_message_body(
+        &self,
+        address: &BtcAddress,
+        message: &str,
+        derivation_path: Option<&str>,
+    ) -> JsonRpcBodyBuilder {
+        let mut builder = JsonRpcBody::new()
+            .method(ElectrumMethod::SignMessage)
+            .add_param(Param::BtcAddress, Value::from(address))
+            .add_param(Param::Message, Value::from(message));
+
+        if let Some(path) = derivation_path {
+            builder = builder.add_param(Param::DerivationPath, Value::from(path));
+        }
+
+        self.with_wallet_password(builder)
+    }
+
+    /// Set daemon config `key` to `value`.
+    pub async fn set_config(&self, key: &str, value: Value) -> Result<()> {
+        let _: Value = self.call(&set_config_body(key, value)).await?;
+        Ok(())
+    }
+
+    /// Make all subsequent payments replaceable by fee (BIP 125).
+    pub async fn enable_rbf(&self) -> Result<()> {
+        self.set_config("use_rbf", Value::from(true)).await
+    }
+
+    /// Broadcast a transaction to the network. A transaction the daemon
+    /// rejects fails with `ElectrumRpcError::RpcError` instead of returning
+    /// the error reply.
+    pub async fn broadcast(&self, tx: &str) -> Result<Response<Body>> {
+        let resp = self
+            .call_method(
+                JsonRpcBody::new()
+                    .method(ElectrumMethod::Broadcast)
+                    .add_param(Param::Transaction, Value::from(tx))
+                    .build()
+                    .borrow(),
+            )
+            .await?;
+
+        reject_rpc_error(resp).await
+    }
+
+    /// Broadcast a transaction to the network, returning its txid.
+    pub async fn broadcast_typed(&self, tx: &str) -> Result<String> {
+        self.call(
+            JsonRpcBody::new()
+                .method(ElectrumMethod::Broadcast)
+                .add_param(Param::Transaction, Value::from(tx))
                 .build()
                 .borrow(),
         )
         .await
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use crate::error::{ElectrumRpcError, InvalidUri};
-    use crate::ext::tests::*;
+    /// Broadcast signed transaction `tx` and check that the daemon knows it
+    /// afterwards, returning the txid. Fails with `ElectrumRpcError::NotInMempool`
+    /// if the transaction was silently dropped after broadcast.
+    ///
+    /// The check goes through `gettxstatus`, which knows only transactions of
+    /// the loaded wallet: a transaction not touching the wallet always fails
+    /// with `NotInMempool`.
+    pub async fn broadcast_and_verify(&self, tx: &str) -> Result<String> {
+        let txid = self.broadcast_typed(tx).await?;
+
+        match self.get_tx_status(&txid).await {
+            Ok(_) => Ok(txid),
+            Err(ElectrumRpcError::RpcError { message, .. }) if is_unknown_transaction(&message) => {
+                Err(ElectrumRpcError::NotInMempool(txid))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Sign a transaction and broadcast it, returning the txid.
+    /// `password` takes precedence over the configured password provider.
+    pub async fn sign_and_broadcast(&self, tx: &str, password: Option<&str>) -> Result<String> {
+        let mut builder = JsonRpcBody::new()
+            .method(ElectrumMethod::SignTransaction)
+            .add_param(Param::Transaction, Value::from(tx));
+
+        builder = match password {
+            Some(password) => builder.add_param(Param::Password, Value::from(password)),
+            None => self.with_wallet_password(builder),
+        };
+
+        let signed: String = self.call(&builder.build()).await?;
+
+        self.broadcast_typed(&signed).await
+    }
+
+    /// Create a transaction, `PayAmount::Max` spends the whole wallet.
+    pub async fn pay_to<'a>(
+        &self,
+        destination: &BtcAddress<'a>,
+        amount: impl Into<PayAmount>,
+        options: &PayToOptions,
+    ) -> Result<Response<Body>> {
+        let amount = amount.into().checked()?;
+        self.check_network(destination.address)?;
+        if let PayAmount::Btc(btc) = amount {
+            self.check_dust(destination.address, btc)?;
+        }
+        let builder = options.to_builder(destination, amount);
+        self.ensure_wallet_loaded().await?;
+
+        self.call_method(&self.with_wallet_password(builder).build())
+            .await
+    }
+
+    /// Create a transaction, returning it typed for both the string and the
+    /// object shape of the `payto` result.
+    pub async fn pay_to_typed<'a>(
+        &self,
+        destination: &BtcAddress<'a>,
+        amount: Decimal,
+        options: &PayToOptions,
+    ) -> Result<CreatedTransaction> {
+        let amount = Amount::from_btc_checked(amount)?;
+        self.check_network(destination.address)?;
+        self.check_dust(destination.address, amount.as_btc())?;
+        let builder = options.to_builder(destination, amount);
+        self.ensure_wallet_loaded().await?;
+
+        self.call(&self.with_wallet_password(builder).build()).await
+    }
+
+    /// Create a transaction with a zero-value OP_RETURN output carrying
+    /// `data_hex`, e.g. to anchor a document hash. `fee` in BTC, estimated
+    /// by the daemon when `None`.
+    pub async fn pay_to_data(
+        &self,
+        data_hex: &str,
+        fee: Option<Decimal>,
+    ) -> Result<CreatedTransaction> {
+        let builder = pay_to_data_body(data_hex, fee)?;
+        self.ensure_wallet_loaded().await?;
+
+        self.call(&self.with_wallet_password(builder).build()).await
+    }
+
+    /// Create a transaction and parse it, e.g. to check the fee rate
+    /// against its `vsize` before broadcasting.
+    pub async fn pay_to_prepared<'a>(
+        &self,
+        destination: &BtcAddress<'a>,
+        amount: Decimal,
+        options: &PayToOptions,
+    ) -> Result<PreparedTx> {
+        let amount = Amount::from_btc_checked(amount)?;
+        self.check_network(destination.address)?;
+        self.check_dust(destination.address, amount.as_btc())?;
+        let builder = options.to_builder(destination, amount);
+        self.ensure_wallet_loaded().await?;
+        let hex: String = self
+            .call(&self.with_wallet_password(builder).build())
+            .await?;
+
+        Ok(PreparedTx::from_hex(&hex)?.with_destination(OwnedBtcAddress::from(destination)))
+    }
+
+    /// Cancel unconfirmed RBF transaction `txid` by replacing it (BIP 125) with
+    /// a transaction spending all of its inputs to an unused wallet address at
+    /// `fee_rate` (sat/vByte). Returns the txid of the broadcast replacement.
+    ///
+    /// The wallet takes the inputs of `txid` as spent, so neither `payto` nor
+    /// `bumpfee`, which keeps the outputs, can build the replacement: it is
+    /// built with `serialize` from the inputs of `txid` and signed by the
+    /// wallet. Fails with `ElectrumErrorCode::AlreadyConfirmed` if `txid` is
+    /// already mined, with `ElectrumRpcError::InvalidParams` if it doesn't
+    /// signal replaceability or `fee_rate` doesn't pay for the replacement,
+    /// and with `ElectrumErrorCode::DustOutput` if little enough would be left.
+    pub async fn cancel_transaction(&self, txid: &str, fee_rate: Decimal) -> Result<String> {
+        if self.get_tx_status(txid).await?.confirmations > 0 {
+            return Err(ElectrumRpcError::ElectrumError(
+                ElectrumErrorCode::AlreadyConfirmed,
+            ));
+        }
+
+        let hex: String = self
+            .call(&GetTransactionOptions::default().to_body(txid))
+            .await?;
+        let original = self.deserialize_transaction(&hex).await?;
+        if !original
+            .inputs
+            .iter()
+            .any(|input| input.nsequence < RBF_SEQUENCE_LIMIT)
+        {
+            return Err(ElectrumRpcError::InvalidParams(format!(
+                "{} doesn't signal replaceability (BIP 125)",
+                txid
+            )));
+        }
+
+        let values = self.input_values(&original).await?;
+        let spent: u64 = values.iter().sum();
+        let original_fee = fee_of(txid, &original, spent)?;
+        let inputs: Vec<Value> = original
+            .inputs
+            .iter()
+            .zip(&values)
+            .map(|(input, value)| {
+                json!({
+                    "prevout_hash": input.prevout_hash,
+                    "prevout_n": input.prevout_n,
+                    "value_sats": value,
+                    "nsequence": input.nsequence,
+                })
+            })
+            .collect();
+
+        self.ensure_wallet_loaded().await?;
+        let address = self.get_unused_address().await?;
+
+        // Signed once without fee for its size; each signature may come out
+        // a byte longer the second time.
+        let draft = self
+            .sign_replacement(&inputs, &address, spent, original.locktime)
+            .await?;
+        let vsize = draft.vsize() + inputs.len();
+        let fee = (fee_rate * Decimal::from(vsize))
+            .ceil()
+            .to_u64()
+            .ok_or_else(|| {
+                ElectrumRpcError::InvalidParams(format!("invalid fee rate {}", fee_rate))
+            })?;
+        // BIP 125: pay the fee of the original plus the relay fee of the replacement.
+        if fee < original_fee + vsize as u64 {
+            return Err(ElectrumRpcError::InvalidParams(format!(
+                "{} sat/vB pays {} sat, replacing {} needs at least {} sat",
+                fee_rate,
+                fee,
+                txid,
+                original_fee + vsize as u64
+            )));
+        }
+
+        let value = spent.saturating_sub(fee);
+        if btc::is_dust(
+            Decimal::new(value as i64, 8),
+            AddressType::of(&address.address)?,
+        ) {
+            return Err(ElectrumRpcError::ElectrumError(
+                ElectrumErrorCode::DustOutput,
+            ));
+        }
+
+        let replacement = self
+            .sign_replacement(&inputs, &address, value, original.locktime)
+            .await?;
+        self.broadcast_typed(replacement.hex()).await
+    }
+
+    /// Transaction spending `inputs`, given as `serialize` takes them, to
+    /// `value` satoshis on `address`, signed by the wallet.
+    async fn sign_replacement(
+        &self,
+        inputs: &[Value],
+        address: &OwnedBtcAddress,
+        value: u64,
+        locktime: u32,
+    ) -> Result<PreparedTx> {
+        let tx = json!({
+            "inputs": inputs,
+            "outputs": [{"address": address, "value_sats": value}],
+            "locktime": locktime,
+        });
+        let serialized: String = self
+            .call(
+                JsonRpcBody::new()
+                    .method(ElectrumMethod::Serialize)
+                    .add_param(Param::JsonTx, tx)
+                    .build()
+                    .borrow(),
+            )
+            .await?;
+
+        let signed = self.sign_psbt_typed(&serialized, None).await?;
+        if !signed.complete {
+            return Err(ElectrumRpcError::MalformedResponse(
+                "the wallet couldn't sign all inputs of the replacement".to_string(),
+            ));
+        }
+
+        PreparedTx::from_hex(&signed.psbt)
+    }
+
+    /// Create a multi-output transaction.
+    pub async fn pay_to_many(&self, fee: Decimal, outputs: Vec<Output>) -> Result<Response<Body>> {
+        for output in &outputs {
+            self.check_network(&output.address)?;
+            self.check_dust(&output.address, output.amount)?;
+        }
+        let outputs = json!(outputs);
+        let fee = fee.to_string();
+        let builder = JsonRpcBody::new()
+            .method(ElectrumMethod::PayToMany)
+            .add_param(Param::Fee, Value::from(fee))
+            .add_param(Param::Outputs, outputs);
+        self.ensure_wallet_loaded().await?;
+
+        self.call_method(&self.with_wallet_password(builder).build())
+            .await
+    }
+
+    /// Close opened wallet.
+    pub async fn close_wallet(&self) -> Result<Response<Body>> {
+        self.call_method(
+            JsonRpcBody::new()
+                .method(ElectrumMethod::CloseWallet)
+                .build()
+                .borrow(),
+        )
+        .await
+    }
+
+    /// Create a payment request, using the first unused address of the wallet.
+    /// The address will be considered as used after this operation.
+    /// If no payment is received, the address will be considered as unused
+    /// if the payment request is deleted from the wallet.
+    pub async fn add_request(
+        &self,
+        amount: Decimal,
+        memo: Option<&str>,
+        expiration: Option<u64>,
+    ) -> Result<Response<Body>> {
+        let mut request = PaymentRequest::new(amount);
+
+        if let Some(memo) = memo {
+            request = request.memo(memo);
+        }
+
+        if let Some(expiration) = expiration {
+            request = request.expiration(expiration);
+        }
+
+        self.add_payment_request(&request).await
+    }
+
+    /// Create a payment request described by `request`.
+    /// See `add_request` for how the receiving address is chosen.
+    pub async fn add_payment_request(
+        &self,
+        request: &PaymentRequest<'_>,
+    ) -> Result<Response<Body>> {
+        Amount::from_btc_checked(request.amount)?;
+        self.ensure_wallet_loaded().await?;
+        self.call_method(&request.to_body()).await
+    }
+
+    /// List the payment requests you made.
+    /// You can combine `pending`, `expired` and `paid` flags for filtering.
+    pub async fn list_requests(
+        &self,
+        pending: bool,
+        expired: bool,
+        paid: bool,
+    ) -> Result<Response<Body>> {
+        self.call_method(
+            JsonRpcBody::new()
+                .method(ElectrumMethod::ListRequests)
+                .add_param(Param::Pending, Value::from(pending))
+                .add_param(Param::Expired, Value::from(expired))
+                .add_param(Param::Paid, Value::from(paid))
+                .build()
+                .borrow(),
+        )
+        .await
+    }
+
+    /// Lightning payment requests of the wallet with their settlement status.
+    #[cfg(feature = "lightning")]
+    pub async fn list_lightning_invoices(&self) -> Result<Vec<LnInvoice>> {
+        #[derive(Deserialize)]
+        struct Request {
+            #[serde(default)]
+            is_lightning: bool,
+            #[serde(flatten)]
+            invoice: Value,
+        }
+
+        let requests: Vec<Request> = self
+            .call_partial(
+                JsonRpcBody::new()
+                    .method(ElectrumMethod::ListRequests)
+                    .add_param(Param::Pending, Value::from(false))
+                    .add_param(Param::Expired, Value::from(false))
+                    .add_param(Param::Paid, Value::from(false))
+                    .build()
+                    .borrow(),
+            )
+            .await?;
+
+        requests
+            .into_iter()
+            .filter(|request| request.is_lightning)
+            .map(|request| Ok(serde_json::from_value(request.invoice)?))
+            .collect()
+    }
+
+    pub async fn remove_request<'a>(&self, address: &BtcAddress<'a>) -> Result<Response<Body>> {
+        self.call_method(
+            JsonRpcBody::new()
+                .method(ElectrumMethod::RemoveRequest)
+                .add_param(Param::BtcAddress, Value::from(address))
+                .build()
+                .borrow(),
+        )
+        .await
+    }
+
+    /// Reissue the unpaid or expired payment request for `address` with a new
+    /// `amount` and/or `expiration`, keeping its memo and whatever isn't given.
+    /// Requests with any other status, e.g. paid or with a payment in flight,
+    /// fail with `ElectrumRpcError::InvalidParams` and are left alone.
+    ///
+    /// The daemon can't update a request in place, so it is removed and added
+    /// again, which isn't atomic: if adding fails the request stays removed,
+    /// and a payment arriving in between is received by the address without
+    /// settling a request. As the request is new, its expiration counts from
+    /// now even when the current one is kept. The daemon reissues the request
+    /// on the first unused address of the wallet; if that isn't `address`
+    /// anymore the new request is removed too and this fails with
+    /// `ElectrumRpcError::MalformedResponse`, leaving `address` without a request.
+    pub async fn update_request<'a>(
+        &self,
+        address: &BtcAddress<'a>,
+        amount: Option<Decimal>,
+        expiration: Option<u64>,
+    ) -> Result<Invoice<'a>> {
+        #[derive(Deserialize)]
+        struct CurrentRequest {
+            #[serde(default, rename = "amount_BTC")]
+            amount: Option<Decimal>,
+            #[serde(default, alias = "memo")]
+            message: Option<String>,
+            #[serde(default, alias = "exp")]
+            expiration: Option<u64>,
+            status: u64,
+        }
+
+        #[derive(Deserialize)]
+        struct AddedRequest {
+            address: OwnedBtcAddress,
+        }
+
+        self.ensure_wallet_loaded().await?;
+        let current: CurrentRequest = self
+            .call_partial(
+                JsonRpcBody::new()
+                    .method(ElectrumMethod::GetRequest)
+                    .add_param(Param::Key, Value::from(address))
+                    .build()
+                    .borrow(),
+            )
+            .await?;
+
+        if current.status == ELECTRUM_REQUEST_PAID {
+            return Err(ElectrumRpcError::InvalidParams(format!(
+                "request for {} is already paid",
+                address.address
+            )));
+        }
+        if current.status != ELECTRUM_REQUEST_UNPAID && current.status != ELECTRUM_REQUEST_EXPIRED {
+            return Err(ElectrumRpcError::InvalidParams(format!(
+                "request for {} has status {}, only unpaid or expired requests can be reissued",
+                address.address, current.status
+            )));
+        }
+
+        let amount = Amount::from_btc_checked(amount.or(current.amount).unwrap_or_default())?;
+        let mut request = PaymentRequest::new(amount.as_btc());
+        if let Some(memo) = current.message.as_deref().filter(|memo| !memo.is_empty()) {
+            request = request.memo(memo);
+        }
+        if let Some(expiration) = expiration.or(current.expiration) {
+            request = request.expiration(expiration);
+        }
+
+        let _: bool = self
+            .call(
+                JsonRpcBody::new()
+                    .method(ElectrumMethod::RemoveRequest)
+                    .add_param(Param::BtcAddress, Value::from(address))
+                    .build()
+                    .borrow(),
+            )
+            .await?;
+        let added: AddedRequest = self.call_partial(&request.to_body()).await?;
+
+        if added.address.address != address.address {
+            let _: bool = self
+                .call(
+                    JsonRpcBody::new()
+                        .method(ElectrumMethod::RemoveRequest)
+                        .add_param(
+                            Param::BtcAddress,
+                            Value::from(&added.address.as_btc_address()),
+                        )
+                        .build()
+                        .borrow(),
+                )
+                .await?;
+
+            return Err(ElectrumRpcError::MalformedResponse(format!(
+                "request for {} was reissued on {}, removed it again",
+                address.address, added.address.address
+            )));
+        }
+
+        Ok(Invoice {
+            amount: amount.as_btc(),
+            address: address.clone(),
+        })
+    }
+
+    /// Return current suggested fee rate (in sat/kvByte),
+    /// according to config settings of electrum, as the raw daemon response.
+    pub async fn get_fee_rate_raw(&self) -> Result<Response<Body>> {
+        self.call_method(
+            JsonRpcBody::new()
+                .method(ElectrumMethod::GetFeeRate)
+                .build()
+                .borrow(),
+        )
+        .await
+    }
+
+    /// Fee rate in sat/kvByte to confirm within `target` blocks, or as set in
+    /// the electrum config without a target. Electrum estimates only for 25,
+    /// 10, 5, 2 and 1 blocks, the nearest of them not above `target` is used.
+    /// Fails with `ElectrumRpcError::NoFeeEstimate` if the daemon has none yet.
+    pub async fn get_fee_rate(&self, target: Option<u32>) -> Result<Decimal> {
+        let mut builder = JsonRpcBody::new().method(ElectrumMethod::GetFeeRate);
+        if let Some(target) = target {
+            builder = builder
+                .add_param(Param::FeeMethod, Value::from("eta"))
+                .add_param(Param::FeeLevel, Value::from(eta_fee_level(target)));
+        }
+
+        let result: Value = self.call(&builder.build()).await?;
+        if result.is_null() {
+            return Err(ElectrumRpcError::NoFeeEstimate);
+        }
+
+        Ok(FeeRate::from_getfeerate(&result)?.as_sat_per_kvb())
+    }
+
+    /// Current suggested fee rate from `getfeerate`, normalized to sat/vByte.
+    pub async fn suggested_fee_rate(&self) -> Result<FeeRate> {
+        let result: Value = self
+            .call(
+                JsonRpcBody::new()
+                    .method(ElectrumMethod::GetFeeRate)
+                    .build()
+                    .borrow(),
+            )
+            .await?;
+
+        FeeRate::from_getfeerate(&result)
+    }
+
+    /// Mempool fee histogram as `(fee_rate, vsize)` buckets,
+    /// fee rate in sat/vByte, ordered from the highest fee rate.
+    pub async fn fee_histogram(&self) -> Result<Vec<(Decimal, u64)>> {
+        self.call(
+            JsonRpcBody::new()
+                .method(ElectrumMethod::GetFeeHistogram)
+                .build()
+                .borrow(),
+        )
+        .await
+    }
+
+    /// Get Merkle branch of a transaction included in a block at `height`.
+    /// Served from the cache when enabled with `ElectrumBuilder::with_merkle_cache`.
+    pub async fn get_merkle(&self, txid: &str, height: u64) -> Result<MerkleProof> {
+        let key = (txid.to_string(), height);
+        if let Some(cache) = &self.merkle_cache {
+            if let Some(proof) = cache.lock().unwrap().get(&key) {
+                return Ok(proof.clone());
+            }
+        }
+
+        let proof: MerkleProof = self
+            .call(
+                JsonRpcBody::new()
+                    .method(ElectrumMethod::GetMerkle)
+                    .add_param(Param::Txid, Value::from(txid))
+                    .add_param(Param::Height, Value::from(height))
+                    .build()
+                    .borrow(),
+            )
+            .await?;
+
+        if let Some(cache) = &self.merkle_cache {
+            cache.lock().unwrap().put(key, proof.clone());
+        }
+
+        Ok(proof)
+    }
+
+    /// Returns some information regarding the tx. For now, only confirmations.
+    /// The transaction must be related to the wallet.
+    pub async fn get_tx_status(&self, txid: &str) -> Result<TxStatus> {
+        self.call(
+            JsonRpcBody::new()
+                .method(ElectrumMethod::GetTxStatus)
+                .add_param(Param::Txid, Value::from(txid))
+                .build()
+                .borrow(),
+        )
+        .await
+    }
+
+    /// Fee rate in sat/vByte of wallet transaction `txid` while it is in the
+    /// mempool, e.g. to decide whether to bump it. `None` once it is confirmed
+    /// or if the daemon doesn't know it.
+    ///
+    /// The fee is the value of the outputs spent, read from the transactions
+    /// they belong to, less the value of the outputs: the daemon fetches
+    /// those not in the wallet from its server.
+    pub async fn get_mempool_fee_rate(&self, txid: &str) -> Result<Option<Decimal>> {
+        match self.get_tx_status(txid).await {
+            Ok(status) if status.confirmations == 0 => {}
+            Ok(_) | Err(ElectrumRpcError::RpcError { .. }) => return Ok(None),
+            Err(e) => return Err(e),
+        }
+
+        let hex = self.get_transaction(txid).await?;
+        let decoded = self.deserialize_transaction(&hex).await?;
+
+        let spent: u64 = self.input_values(&decoded).await?.iter().sum();
+        let fee = fee_of(txid, &decoded, spent)?;
+
+        let tx = PreparedTx::from_hex(&hex)?;
+        Ok(Some(Decimal::from(fee) / Decimal::from(tx.vsize())))
+    }
+
+    /// Values in satoshis of the outputs spent by the inputs of `tx`, read
+    /// from the transactions they belong to. The daemon fetches those not in
+    /// the wallet from its server.
+    async fn input_values(&self, tx: &DeserializedTx) -> Result<Vec<u64>> {
+        let mut values = Vec::with_capacity(tx.inputs.len());
+        for input in &tx.inputs {
+            let previous = self.get_transaction(&input.prevout_hash).await?;
+            let previous = self.deserialize_transaction(&previous).await?;
+            let output = previous
+                .outputs
+                .get(input.prevout_n as usize)
+                .ok_or_else(|| {
+                    ElectrumRpcError::MalformedResponse(format!(
+                        "{} has no output {}",
+                        input.prevout_hash, input.prevout_n
+                    ))
+                })?;
+            values.push(output.value_sats);
+        }
+
+        Ok(values)
+    }
+
+    /// Retrieve raw hex of transaction `txid`.
+    /// Fails with `ElectrumRpcError::InvalidTxid` without calling the daemon
+    /// if `txid` isn't 64 hex characters.
+    pub async fn get_transaction(&self, txid: &str) -> Result<String> {
+        let txid: Txid = txid.parse()?;
+
+        self.call(&GetTransactionOptions::default().to_body(txid.as_str()))
+            .await
+    }
+
+    /// Decode raw transaction `tx`, e.g. to check its outputs before
+    /// broadcasting it.
+    pub async fn deserialize_transaction(&self, tx: &str) -> Result<DeserializedTx> {
+        self.call(
+            JsonRpcBody::new()
+                .method(ElectrumMethod::Deserialize)
+                .add_param(Param::Transaction, Value::from(tx))
+                .build()
+                .borrow(),
+        )
+        .await
+    }
+
+    /// Retrieve a transaction, as raw hex string or decoded json object
+    /// depending on `options.verbose`.
+    pub async fn get_transaction_with_options(
+        &self,
+        txid: &str,
+        options: &GetTransactionOptions,
+    ) -> Result<Value> {
+        let hex: String = self.call(&options.to_body(txid)).await?;
+
+        if !options.verbose {
+            return Ok(Value::from(hex));
+        }
+
+        self.call(
+            JsonRpcBody::new()
+                .method(ElectrumMethod::Deserialize)
+                .add_param(Param::Transaction, Value::from(hex))
+                .build()
+                .borrow(),
+        )
+        .await
+    }
+
+    /// Poll the balance of `address` as configured by `config` until confirmed
+    /// and unconfirmed together reach `min`, e.g. to await a deposit without
+    /// `notify`. Fails with `ElectrumRpcError::Timeout` once `config` gives up
+    /// or `ElectrumRpcError::Cancelled` when `cancel` is triggered.
+    pub async fn wait_for_balance<'a>(
+        &self,
+        address: &BtcAddress<'a>,
+        min: Decimal,
+        config: &PollConfig,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<Balance> {
+        poll::poll_until(config, cancel, || async {
+            let balance = self.get_address_balance_typed(address).await?;
+            Ok(Some(balance).filter(|balance| balance.confirmed + balance.unconfirmed >= min))
+        })
+        .await
+    }
+
+    /// Poll the status of transaction `txid` as configured by `config` until it
+    /// has `target` confirmations. Fails with `ElectrumRpcError::Timeout` once
+    /// `config` gives up or `ElectrumRpcError::Cancelled` when `cancel` is triggered.
+    pub async fn wait_for_confirmations(
+        &self,
+        txid: &str,
+        target: u64,
+        config: &PollConfig,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<TxStatus> {
+        poll::poll_until(config, cancel, || async {
+            let status = self.get_tx_status(txid).await?;
+            Ok(Some(status).filter(|status| status.confirmations >= target))
+        })
+        .await
+    }
+
+    /// Pay `amount` to `destination`, broadcast the transaction and wait until
+    /// it has `target_confs` confirmations. The payment is created with
+    /// `pay_to_typed`, so the same network and dust checks apply.
+    ///
+    /// Any failure is an `ElectrumRpcError::PaymentFailed` telling the step it
+    /// happened in: nothing was sent if creating the transaction failed, it
+    /// may be in flight if broadcasting failed, and it is in flight if waiting
+    /// for confirmations failed, e.g. with `ElectrumRpcError::Timeout`. The
+    /// error carries the txid once known.
+    pub async fn pay_and_confirm<'a>(
+        &self,
+        destination: &BtcAddress<'a>,
+        amount: Decimal,
+        target_confs: u64,
+        timeout: Duration,
+    ) -> Result<TxStatus> {
+        let failed = |stage, txid: Option<&str>| {
+            let txid = txid.map(str::to_string);
+            move |source| ElectrumRpcError::PaymentFailed {
+                stage,
+                txid,
+                source: Box::new(source),
+            }
+        };
+
+        let tx = self
+            .pay_to_typed(destination, amount, &PayToOptions::new())
+            .await
+            .map_err(failed(PaymentStage::Create, None))?;
+
+        let txid: String = self
+            .call(
+                JsonRpcBody::new()
+                    .method(ElectrumMethod::Broadcast)
+                    .add_param(Param::Transaction, Value::from(tx.hex))
+                    .build()
+                    .borrow(),
+            )
+            .await
+            .map_err(failed(PaymentStage::Broadcast, tx.final_txid.as_deref()))?;
+        info!("Paid {} to {}, txid {}", amount, destination.address, txid);
+
+        self.wait_for_confirmations(
+            &txid,
+            target_confs,
+            &PollConfig::new(PAY_AND_CONFIRM_POLL_INTERVAL, timeout),
+            None,
+        )
+        .await
+        .map_err(failed(PaymentStage::Confirm, Some(&txid)))
+    }
+
+    /// Poll the loaded wallet as configured by `config` until it is synchronized
+    /// with the network, e.g. right after `load_wallet`. Fails with
+    /// `ElectrumRpcError::Timeout` once `config` gives up or
+    /// `ElectrumRpcError::Cancelled` when `cancel` is triggered.
+    pub async fn wait_until_synced(
+        &self,
+        config: &PollConfig,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<()> {
+        poll::poll_until(config, cancel, || async {
+            let synced: bool = self
+                .call(
+                    JsonRpcBody::new()
+                        .method(ElectrumMethod::IsSynchronized)
+                        .build()
+                        .borrow(),
+                )
+                .await?;
+
+            Ok(Some(()).filter(|_| synced))
+        })
+        .await
+    }
+
+    /// Poll the payment request for `address` as configured by `config` until it
+    /// is paid. Fails with `ElectrumRpcError::Timeout` once `config` gives up
+    /// or `ElectrumRpcError::Cancelled` when `cancel` is triggered.
+    pub async fn wait_for_payment<'a>(
+        &self,
+        address: &BtcAddress<'a>,
+        config: &PollConfig,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<()> {
+        #[derive(Deserialize)]
+        struct RequestStatus {
+            status: u64,
+        }
+
+        poll::poll_until(config, cancel, || async {
+            let request: RequestStatus = self
+                .call_partial(
+                    JsonRpcBody::new()
+                        .method(ElectrumMethod::GetRequest)
+                        .add_param(Param::Key, Value::from(address))
+                        .build()
+                        .borrow(),
+                )
+                .await?;
+
+            Ok(Some(()).filter(|_| request.status == ELECTRUM_REQUEST_PAID))
+        })
+        .await
+    }
+
+    /// Host of the Electrum server the daemon is connected to and the
+    /// protocol version spoken with it, as reported by `getinfo`.
+    pub async fn server_info(&self) -> Result<(String, String)> {
+        let info = self.get_info().await?;
+
+        match (info.server, info.protocol_version) {
+            (Some(server), Some(protocol_version)) => Ok((server, protocol_version)),
+            _ => Err(ElectrumRpcError::MalformedResponse(
+                "getinfo reports no server or protocol version".to_string(),
+            )),
+        }
+    }
+
+    /// Blocks the local chain of the daemon is behind its server, i.e.
+    /// `server_height - blockchain_height` from `getinfo`. Positive while catching up.
+    pub async fn sync_lag(&self) -> Result<i64> {
+        let info = self.get_info().await?;
+
+        Ok(info.server_height as i64 - info.blockchain_height as i64)
+    }
+
+    /// Whether the daemon has Lightning enabled. Uses the `lightning` flag of
+    /// `getinfo` when reported, otherwise looks for the `nodeid` command in `help`.
+    pub async fn has_lightning(&self) -> Result<bool> {
+        if let Some(lightning) = self.get_info().await?.lightning {
+            return Ok(lightning);
+        }
+
+        let commands: Vec<String> = self
+            .call(
+                JsonRpcBody::new()
+                    .method(ElectrumMethod::Help)
+                    .build()
+                    .borrow(),
+            )
+            .await?;
+
+        Ok(commands.iter().any(|command| command == "nodeid"))
+    }
+
+    /// Estimate in how many blocks a transaction paying `fee_rate` (sat/vByte)
+    /// confirms, interpolated from the `fee_estimates` reported by `getinfo`.
+    /// `None` if the fee rate is below all estimates.
+    pub async fn estimate_confirmation_blocks(&self, fee_rate: Decimal) -> Result<Option<u32>> {
+        let info = self.get_info().await?;
+
+        Ok(info.fee_estimates.confirmation_blocks(fee_rate))
+    }
+
+    /// Wallet onchain history.
+    /// Returns the transaction history of your wallet.
+    pub async fn get_onchain_history(&self) -> Result<Response<Body>> {
+        self.call_method(
+            JsonRpcBody::new()
+                .method(ElectrumMethod::GetOnchainHistory)
+                .build()
+                .borrow(),
+        )
+        .await
+    }
+
+    /// Wallet transactions from `onchain_history` as records for accounting
+    /// exports, see `responses::ToCsv`.
+    pub async fn transaction_report(&self) -> Result<Vec<TxRecord>> {
+        #[derive(Deserialize)]
+        struct History {
+            transactions: Vec<TxRecord>,
+        }
+
+        let history: History = self
+            .call_partial(
+                JsonRpcBody::new()
+                    .method(ElectrumMethod::GetOnchainHistory)
+                    .build()
+                    .borrow(),
+            )
+            .await?;
+
+        Ok(history.transactions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::error::InvalidUri;
+    use crate::ext::tests::*;
+
+    use super::*;
+
+    #[test]
+    fn new_electrum_instance0() {
+        let electrum = get_electrum_rpc();
+        let port = electrum.address.port();
+        assert_eq!(port.unwrap().as_u16(), 7000);
+
+        let host = electrum.address.host();
+        assert_eq!(host, Some("127.0.0.1"));
+
+        let auth = electrum.auth.header();
+        assert!(auth.is_sensitive());
+        let encoded_creds = auth.to_str().unwrap().split(' ').collect::<Vec<&str>>()[1];
+        let decoded_creds = base64::decode(encoded_creds).unwrap();
+        assert_eq!("test:test", std::str::from_utf8(&decoded_creds).unwrap());
+    }
+
+    #[test]
+    #[should_panic]
+    fn new_electrum_instance_empty_address() {
+        Electrum::new(LOGIN.clone(), PASSWORD.clone(), "".to_string()).unwrap();
+    }
+
+    #[test]
+    fn error_casting_address_error() {
+        let electrum = Electrum::new(LOGIN.clone(), PASSWORD.clone(), "".to_string());
+
+        assert!(matches!(
+            electrum,
+            Err(ElectrumRpcError::AddressError(InvalidUri { .. }))
+        ))
+    }
+
+    #[tokio::test]
+    async fn connection_pool_busy() {
+        let daemon = MockDaemon::start(|_, _| {
+            MockReply::result(json!({})).delay(Duration::from_millis(500))
+        });
+        let electrum = Electrum::builder(LOGIN.clone(), PASSWORD.clone(), daemon.address())
+            .with_max_connections(1)
+            .with_pool_wait(Duration::from_millis(50))
+            .build()
+            .unwrap();
+
+        let (first, second) = tokio::join!(electrum.get_balance(), electrum.get_balance());
+
+        assert!(first.is_ok());
+        assert!(matches!(second, Err(ElectrumRpcError::ConnectionPoolBusy)));
+        assert_eq!(daemon.calls(), 1);
+
+        // the connection is busy until the body is read
+        let daemon = MockDaemon::start(|_, _| {
+            MockReply::result(json!({})).body_delay(Duration::from_millis(500))
+        });
+        let electrum = Electrum::builder(LOGIN.clone(), PASSWORD.clone(), daemon.address())
+            .with_max_connections(1)
+            .with_pool_wait(Duration::from_millis(50))
+            .build()
+            .unwrap();
+
+        let (first, second) = tokio::join!(electrum.get_balance(), electrum.get_balance());
+
+        assert!(first.is_ok());
+        assert!(matches!(second, Err(ElectrumRpcError::ConnectionPoolBusy)));
+        assert_eq!(daemon.calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn password_provider_used_for_spend_only() {
+        let daemon = MockDaemon::start(|_, _| MockReply::result(json!(true)));
+        let invocations = Arc::new(AtomicUsize::new(0));
+        let counter = invocations.clone();
+        let electrum = Electrum::builder(LOGIN.clone(), PASSWORD.clone(), daemon.address())
+            .with_password_provider(move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+                "secret".to_string()
+            })
+            .build()
+            .unwrap();
+
+        electrum.get_balance().await.unwrap();
+        assert_eq!(invocations.load(Ordering::SeqCst), 0);
+        assert_eq!(daemon.requests()[0].body["params"]["password"], Value::Null);
+
+        let addr = BtcAddress::new("tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn");
+        electrum
+            .pay_to(&addr, Decimal::new(1, 5), &PayToOptions::new())
+            .await
+            .unwrap();
+        assert_eq!(invocations.load(Ordering::SeqCst), 1);
+        assert_eq!(daemon.requests()[1].body["params"]["password"], "secret");
+    }
+
+    #[tokio::test]
+    async fn daemon_unreachable() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let address = format!("http://127.0.0.1:{}", port);
+        let electrum = Electrum::new(LOGIN.clone(), PASSWORD.clone(), address).unwrap();
+
+        let res = electrum.get_info().await;
+        assert!(matches!(res, Err(ElectrumRpcError::DaemonUnreachable(_))));
+    }
+
+    #[tokio::test]
+    async fn sign_and_broadcast_success() {
+        let daemon = MockDaemon::start(|_, req| match req["method"].as_str() {
+            Some("signtransaction") => MockReply::result(json!("signed_hex")),
+            _ => MockReply::result(json!("f".repeat(64))),
+        });
+        let electrum = daemon.electrum();
+
+        let txid = electrum
+            .sign_and_broadcast("unsigned_hex", Some("secret"))
+            .await
+            .unwrap();
+        assert_eq!(txid, "f".repeat(64));
+
+        let requests = daemon.requests();
+        assert_eq!(requests[0].body["params"]["password"], "secret");
+        assert_eq!(requests[1].body["method"], "broadcast");
+        assert_eq!(requests[1].body["params"]["tx"], "signed_hex");
+    }
+
+    #[tokio::test]
+    async fn sign_and_broadcast_sign_failure() {
+        let daemon = MockDaemon::start(|_, _| MockReply::error(1, "Invalid password"));
+        let electrum = daemon.electrum();
+
+        let res = electrum.sign_and_broadcast("unsigned_hex", None).await;
+        assert!(res.is_err());
+        assert_eq!(daemon.calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn merkle_proof_cached() {
+        let daemon = MockDaemon::start(|_, _| {
+            MockReply::result(json!({"block_height": 1000, "merkle": ["ab", "cd"], "pos": 3}))
+        });
+        let electrum = Electrum::builder(LOGIN.clone(), PASSWORD.clone(), daemon.address())
+            .with_merkle_cache(8)
+            .build()
+            .unwrap();
+        let txid = "f".repeat(64);
+
+        let first = electrum.get_merkle(&txid, 1000).await.unwrap();
+        let second = electrum.get_merkle(&txid, 1000).await.unwrap();
+
+        assert_eq!(first, second);
+        assert_eq!(first.pos, 3);
+        assert_eq!(daemon.calls(), 1);
+        assert_eq!(daemon.requests()[0].body["params"]["height"], 1000);
+    }
+
+    #[tokio::test]
+    async fn watch_balance_yields_on_notification() {
+        let daemon = MockDaemon::start(|_, req| match req["method"].as_str() {
+            Some("notify") => MockReply::result(json!(true)),
+            _ => MockReply::result(json!({"confirmed": "0.1", "unconfirmed": "0.02"})),
+        });
+        let electrum = daemon.electrum();
+        let webhook = WebhookServer::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let url = webhook.url().clone();
+        let addr = BtcAddress::new("tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn");
+
+        let balances = electrum.watch_balance(&addr, webhook).await.unwrap();
+        futures::pin_mut!(balances);
+        assert_eq!(daemon.requests()[0].body["params"]["URL"], url.to_string());
+
+        let notification = json!({"address": addr.address, "status": "f".repeat(64)});
+        let req = Request::post(url)
+            .body(Body::from(notification.to_string()))
+            .unwrap();
+        Client::new().request(req).await.unwrap();
+
+        let balance = balances.next().await.unwrap().unwrap();
+        assert_eq!(balance.confirmed, Decimal::new(1, 1));
+        assert_eq!(balance.unconfirmed, Decimal::new(2, 2));
+    }
+
+    #[tokio::test]
+    async fn load_wallet_by_name_resolves_path() {
+        use crate::ext::fixtures;
+
+        let daemon = MockDaemon::start(|_, req| match req["method"].as_str() {
+            Some("getinfo") => MockReply::raw(fixtures::GET_INFO),
+            _ => MockReply::result(json!(true)),
+        });
+        let electrum = daemon.electrum();
+
+        electrum.load_wallet_by_name("savings", None).await.unwrap();
+
+        let requests = daemon.requests();
+        assert_eq!(requests[1].body["method"], "load_wallet");
+        assert_eq!(
+            requests[1].body["params"]["wallet_path"],
+            "/home/electrum/.electrum/testnet/wallets/savings"
+        );
+    }
+
+    #[tokio::test]
+    async fn prepared_tx_change_address() {
+        const DESTINATION: &str = "tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn";
+        const CHANGE: &str = "tb1q6fezqhhcr8trzy33cpspftk4e7csqqy7zckyz0";
+        const TX: &str = "02000000000101b58c5be9c9ce77a8bacd01779fdcfbf566a936a5b89482d1bc3114525ee5f3ea0000000000fdffffff02e8030000000000001600149e08b7dbcd1aad00ec6888a60e56c779f0f2da276022000000000000160014d272035ef819d6311231c06014aed5cfb100009e0247304402203db69d69b3fa76050b6c3276bc21bb834996f2c84c31c17c813beba01079705002202d864669f12db9939ea78a45e4c4a982cca68304fef25f334bd6cbbc9971bc9b012103815054ce939185772574ef569fe31b601d5bad48f48d5edaef194cded838c31ac40f1e00";
+
+        let daemon = MockDaemon::start(|_, req| match req["method"].as_str() {
+            Some("payto") => MockReply::result(json!(TX)),
+            Some("deserialize") => MockReply::result(json!({
+                "outputs": [
+                    {"address": DESTINATION, "value_sats": 1000},
+                    {"address": CHANGE, "value_sats": 8800},
+                ]
+            })),
+            _ => MockReply::result(json!(req["params"]["address"] == CHANGE)),
+        });
+        let electrum = daemon.electrum();
+        let addr = BtcAddress::new(DESTINATION);
+
+        let tx = electrum
+            .pay_to_prepared(&addr, Decimal::new(1, 5), &PayToOptions::new())
+            .await
+            .unwrap();
+        let change = tx.change_address(&electrum).await.unwrap();
+
+        assert_eq!(change, Some(OwnedBtcAddress::new(CHANGE.to_string())));
+        let ismine = daemon
+            .requests()
+            .iter()
+            .filter(|req| req.body["method"] == "ismine")
+            .count();
+        assert_eq!(ismine, 1);
+    }
+
+    #[tokio::test]
+    async fn broadcast_typed_txid() {
+        const TXID: &str = "eaf3e55e521431bcd18294b8a536a966f5fbdc9f7701cdbaa877cec9e95b8cb5";
+
+        let daemon = MockDaemon::start(|_, _| MockReply::result(json!(TXID)));
+        let electrum = daemon.electrum();
+
+        assert_eq!(electrum.broadcast_typed("0200").await.unwrap(), TXID);
+        assert_eq!(daemon.requests()[0].body["params"]["tx"], "0200");
+    }
+
+    #[tokio::test]
+    async fn broadcast_rpc_error() {
+        let daemon = MockDaemon::start(|_, _| {
+            MockReply::error(
+                -32000,
+                "The transaction was rejected by network rules.\n\nbad-txns-inputs-missingorspent",
+            )
+        });
+        let electrum = daemon.electrum();
+
+        let res = electrum.broadcast("0200").await;
+        assert!(matches!(
+            res,
+            Err(ElectrumRpcError::RpcError { code: -32000, message, .. })
+                if message.contains("missingorspent")
+        ));
+    }
+
+    #[tokio::test]
+    async fn broadcast_returns_reply() {
+        let daemon = MockDaemon::start(|_, _| MockReply::result(json!("aa")));
+
+        let resp = daemon.electrum().broadcast("0200").await.unwrap();
+        let json: Value = read_json(resp).await.unwrap();
+        assert_eq!(json["result"], "aa");
+    }
+
+    #[tokio::test]
+    async fn broadcast_typed_rpc_error() {
+        let daemon = MockDaemon::start(|_, _| {
+            MockReply::error(
+                -32000,
+                "The transaction was rejected by network rules.\n\nbad-txns-inputs-missingorspent",
+            )
+        });
+        let electrum = daemon.electrum();
+
+        let res = electrum.broadcast_typed("0200").await;
+        assert!(matches!(
+            res,
+            Err(ElectrumRpcError::RpcError { code: -32000, message, .. })
+                if message.contains("missingorspent")
+        ));
+    }
+
+    #[tokio::test]
+    async fn pay_to_typed_object_result() {
+        let daemon = MockDaemon::start(|_, _| {
+            MockReply::result(json!({"hex": "0200000001", "complete": false, "final": false}))
+        });
+        let electrum = daemon.electrum();
+        let addr = BtcAddress::new("tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn");
+
+        let created = electrum
+            .pay_to_typed(&addr, Decimal::new(1, 5), &PayToOptions::new())
+            .await
+            .unwrap();
+
+        assert_eq!(created.hex, "0200000001");
+        assert!(!created.complete);
+        assert_eq!(created.final_txid, None);
+        assert_eq!(daemon.requests()[0].body["method"], "payto");
+    }
+
+    #[tokio::test]
+    async fn wait_for_confirmations_reached() {
+        let daemon = MockDaemon::start(|call, _| MockReply::result(json!({"confirmations": call})));
+        let electrum = daemon.electrum();
+
+        let status = electrum
+            .wait_for_confirmations(
+                &"f".repeat(64),
+                2,
+                &PollConfig::new(Duration::from_millis(10), Duration::from_secs(5)),
+                None,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(status.confirmations, 2);
+        assert_eq!(daemon.calls(), 3);
+    }
+
+    #[tokio::test]
+    async fn wait_for_balance_reached() {
+        let daemon = MockDaemon::start(|call, _| {
+            MockReply::result(
+                json!({"confirmed": "0.001", "unconfirmed": format!("0.000{}", call)}),
+            )
+        });
+        let electrum = daemon.electrum();
+        let address = BtcAddress::new("tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn");
+
+        let balance = electrum
+            .wait_for_balance(
+                &address,
+                Decimal::new(12, 4),
+                &PollConfig::new(Duration::from_millis(10), Duration::from_secs(5)),
+                None,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(balance.unconfirmed, Decimal::new(2, 4));
+        assert_eq!(daemon.calls(), 3);
+    }
+
+    #[tokio::test]
+    async fn pay_and_confirm_happy_path() {
+        const TXID: &str = "eaf3e55e521431bcd18294b8a536a966f5fbdc9f7701cdbaa877cec9e95b8cb5";
+        let daemon = MockDaemon::start(|_, req| match req["method"].as_str() {
+            Some("payto") => MockReply::result(json!("0200")),
+            Some("broadcast") => MockReply::result(json!(TXID)),
+            _ => MockReply::result(json!({"confirmations": 2})),
+        });
+        let electrum = daemon.electrum();
+        let addr = BtcAddress::new("tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn");
+
+        let status = electrum
+            .pay_and_confirm(&addr, Decimal::new(1, 3), 1, Duration::from_secs(10))
+            .await
+            .unwrap();
+
+        assert_eq!(status.confirmations, 2);
+        let requests = daemon.requests();
+        assert_eq!(requests[0].body["params"]["destination"], addr.address);
+        assert_eq!(requests[1].body["params"]["tx"], "0200");
+        assert_eq!(requests[2].body["method"], "get_tx_status");
+        assert_eq!(requests[2].body["params"]["txid"], TXID);
+    }
+
+    #[tokio::test]
+    async fn pay_and_confirm_failed_stage() {
+        const TXID: &str = "eaf3e55e521431bcd18294b8a536a966f5fbdc9f7701cdbaa877cec9e95b8cb5";
+        let addr = BtcAddress::new("tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn");
+
+        let daemon = MockDaemon::start(|_, req| match req["method"].as_str() {
+            Some("payto") => MockReply::result(json!("0200")),
+            _ => MockReply::error(-32603, "bad-txns-inputs-missingorspent"),
+        });
+        let res = daemon
+            .electrum()
+            .pay_and_confirm(&addr, Decimal::new(1, 3), 1, Duration::from_secs(10))
+            .await;
+        assert!(matches!(
+            res,
+            Err(ElectrumRpcError::PaymentFailed {
+                stage: PaymentStage::Broadcast,
+                txid: None,
+                ..
+            })
+        ));
+
+        let daemon = MockDaemon::start(|_, req| match req["method"].as_str() {
+            Some("payto") => MockReply::result(json!("0200")),
+            Some("broadcast") => MockReply::result(json!(TXID)),
+            _ => MockReply::error(-32603, "unknown transaction"),
+        });
+        let res = daemon
+            .electrum()
+            .pay_and_confirm(&addr, Decimal::new(1, 3), 1, Duration::from_secs(10))
+            .await;
+        match res {
+            Err(ElectrumRpcError::PaymentFailed {
+                stage: PaymentStage::Confirm,
+                txid: Some(txid),
+                source,
+            }) => {
+                assert_eq!(txid, TXID);
+                assert!(matches!(*source, ElectrumRpcError::RpcError { .. }));
+            }
+            other => panic!("unexpected {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[tokio::test]
+    async fn wait_until_synced_polls() {
+        let daemon = MockDaemon::start(|call, _| MockReply::result(json!(call >= 2)));
+        let electrum = daemon.electrum();
+
+        electrum
+            .wait_until_synced(
+                &PollConfig::new(Duration::from_millis(10), Duration::from_secs(10)),
+                None,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(daemon.calls(), 3);
+        assert_eq!(daemon.requests()[0].body["method"], "is_synchronized");
+
+        let daemon = MockDaemon::start(|_, _| MockReply::result(json!(false)));
+        let res = daemon
+            .electrum()
+            .wait_until_synced(
+                &PollConfig::new(Duration::from_millis(10), Duration::from_millis(50)),
+                None,
+            )
+            .await;
+        assert!(matches!(res, Err(ElectrumRpcError::Timeout)));
+    }
+
+    #[tokio::test]
+    async fn wait_for_confirmations_cancelled() {
+        let daemon = MockDaemon::start(|_, _| MockReply::result(json!({"confirmations": 0})));
+        let electrum = daemon.electrum();
+        let cancel = CancellationToken::new();
+
+        let trigger = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            trigger.cancel();
+        });
+
+        let res = electrum
+            .wait_for_confirmations(
+                &"f".repeat(64),
+                1,
+                &PollConfig::new(Duration::from_millis(20), Duration::from_secs(10)),
+                Some(&cancel),
+            )
+            .await;
+
+        assert!(matches!(res, Err(ElectrumRpcError::Cancelled)));
+        assert!(daemon.calls() >= 1);
+    }
+
+    #[tokio::test]
+    async fn update_request_reissues() {
+        const ADDRESS: &str = "tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn";
+
+        let daemon = MockDaemon::start(|_, req| match req["method"].as_str() {
+            Some("getrequest") => MockReply::result(json!({
+                "address": ADDRESS,
+                "amount_BTC": "0.001",
+                "message": "coffee",
+                "expiration": 3600,
+                "status": 0,
+            })),
+            Some("rmrequest") => MockReply::result(json!(true)),
+            _ => MockReply::result(json!({"address": ADDRESS, "amount_BTC": "0.002"})),
+        });
+        let address = BtcAddress::new(ADDRESS);
+
+        let invoice = daemon
+            .electrum()
+            .update_request(&address, Some(Decimal::new(2, 3)), None)
+            .await
+            .unwrap();
+        assert_eq!(invoice.get_amount(), Decimal::new(2, 3));
+        assert_eq!(invoice.get_address().address, ADDRESS);
+
+        let requests = daemon.requests();
+        let methods: Vec<_> = requests.iter().map(|r| r.body["method"].clone()).collect();
+        assert_eq!(methods, ["getrequest", "rmrequest", "add_request"]);
+        assert_eq!(
+            requests[2].body["params"],
+            json!({"amount": 0.002, "memo": "coffee", "expiration": 3600})
+        );
+    }
+
+    #[tokio::test]
+    async fn update_request_paid() {
+        let daemon = MockDaemon::start(|_, _| MockReply::result(json!({"status": 3})));
+        let address = BtcAddress::new("tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn");
+
+        let res = daemon
+            .electrum()
+            .update_request(&address, None, Some(60))
+            .await;
+        assert!(matches!(res, Err(ElectrumRpcError::InvalidParams(_))));
+        assert_eq!(daemon.calls(), 1);
+
+        // in flight
+        let daemon = MockDaemon::start(|_, _| MockReply::result(json!({"status": 4})));
+        let res = daemon
+            .electrum()
+            .update_request(&address, None, Some(60))
+            .await;
+        assert!(matches!(res, Err(ElectrumRpcError::InvalidParams(_))));
+        assert_eq!(daemon.calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn update_request_other_address() {
+        const ADDRESS: &str = "tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn";
+        const OTHER: &str = "tb1q6fezqhhcr8trzy33cpspftk4e7csqqy7zckyz0";
+
+        let daemon = MockDaemon::start(|_, req| match req["method"].as_str() {
+            Some("getrequest") => MockReply::result(json!({"amount_BTC": "0.001", "status": 1})),
+            Some("rmrequest") => MockReply::result(json!(true)),
+            _ => MockReply::result(json!({"address": OTHER, "amount_BTC": "0.001"})),
+        });
+        let address = BtcAddress::new(ADDRESS);
+
+        let res = daemon
+            .electrum()
+            .update_request(&address, None, Some(60))
+            .await;
+        assert!(matches!(res, Err(ElectrumRpcError::MalformedResponse(_))));
+
+        let requests = daemon.requests();
+        assert_eq!(requests[3].body["method"], "rmrequest");
+        assert_eq!(requests[3].body["params"]["address"], OTHER);
+    }
+
+    #[tokio::test]
+    async fn wait_for_payment_timeout() {
+        let daemon = MockDaemon::start(|_, _| MockReply::result(json!({"status": 0})));
+        let electrum = daemon.electrum();
+        let addr = BtcAddress::new("tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn");
+
+        let res = electrum
+            .wait_for_payment(
+                &addr,
+                &PollConfig::new(Duration::from_millis(10), Duration::from_millis(50)),
+                None,
+            )
+            .await;
+
+        assert!(matches!(res, Err(ElectrumRpcError::Timeout)));
+        assert_eq!(daemon.requests()[0].body["params"]["key"], addr.address);
+    }
+
+    #[tokio::test]
+    async fn poll_max_attempts_before_timeout() {
+        let daemon = MockDaemon::start(|_, _| MockReply::result(json!({"confirmations": 0})));
+        let electrum = daemon.electrum();
+        let config = PollConfig::new(Duration::from_millis(10), Duration::from_secs(60))
+            .with_max_attempts(3);
+
+        let res = electrum
+            .wait_for_confirmations(&"f".repeat(64), 1, &config, None)
+            .await;
+
+        assert!(matches!(res, Err(ElectrumRpcError::Timeout)));
+        assert_eq!(daemon.calls(), 3);
+    }
+
+    #[test]
+    fn poll_backoff_grows_delay() {
+        let config =
+            PollConfig::new(Duration::from_secs(1), Duration::from_secs(60)).with_backoff(2.0);
+
+        assert_eq!(config.delay(0), Duration::from_secs(1));
+        assert_eq!(config.delay(2), Duration::from_secs(4));
+        assert_eq!(PollConfig::default().delay(5), Duration::from_secs(10));
+    }
+
+    #[tokio::test]
+    async fn notify_many_per_address_results() {
+        let daemon = MockDaemon::start(|_, req| {
+            let replies: Vec<Value> = req
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|call| match call["params"]["address"].as_str() {
+                    Some("invalid") => json!({
+                        "jsonrpc": "2.0",
+                        "error": {"code": -32000, "message": "invalid bitcoin address"},
+                        "id": call["id"],
+                    }),
+                    _ => json!({"jsonrpc": "2.0", "result": true, "id": call["id"]}),
+                })
+                .collect();
+            MockReply::raw(&Value::from(replies).to_string())
+        });
+        let electrum = daemon.electrum();
+        let addresses = [
+            BtcAddress::new("tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn"),
+            BtcAddress::new("invalid"),
+        ];
+        let url = Uri::from_static("http://localhost/notify");
+
+        let results = electrum.notify_many(&addresses, Some(url)).await.unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(
+            results[0].0.address,
+            "tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn"
+        );
+        assert!(matches!(results[0].1, Ok(true)));
+        assert_eq!(results[1].0.address, "invalid");
+        assert!(matches!(
+            results[1].1,
+            Err(ElectrumRpcError::RpcError { code: -32000, .. })
+        ));
+
+        let batch = &daemon.requests()[0].body;
+        assert_eq!(batch[0]["method"], "notify");
+        assert_eq!(batch[1]["params"]["URL"], "http://localhost/notify");
+    }
+
+    #[tokio::test]
+    async fn address_balances_split_into_batches() {
+        let daemon = MockDaemon::start(|_, req| {
+            let replies: Vec<Value> = req
+                .as_array()
+                .unwrap()
+                .iter()
+                .rev()
+                .map(|call| {
+                    let confirmed = call["params"]["address"]
+                        .as_str()
+                        .unwrap()
+                        .replace("tb1q", "0.");
+                    json!({"jsonrpc": "2.0", "result": {"confirmed": confirmed}, "id": call["id"]})
+                })
+                .collect();
+            MockReply::raw(&Value::from(replies).to_string())
+        });
+        let electrum = Electrum::builder(LOGIN.clone(), PASSWORD.clone(), daemon.address())
+            .with_max_batch(2)
+            .build()
+            .unwrap();
+        let addresses = ["tb1q1", "tb1q2", "tb1q3", "tb1q4", "tb1q5"];
+        let addresses: Vec<BtcAddress> = addresses.iter().map(|a| BtcAddress::new(a)).collect();
+
+        let balances = electrum.get_address_balances(&addresses).await.unwrap();
+
+        let confirmed: Vec<Decimal> = balances.iter().map(|b| b.confirmed).collect();
+        assert_eq!(
+            confirmed,
+            (1..=5).map(|n| Decimal::new(n, 1)).collect::<Vec<_>>()
+        );
+        let batch_sizes: Vec<usize> = daemon
+            .requests()
+            .iter()
+            .map(|req| req.body.as_array().unwrap().len())
+            .collect();
+        assert_eq!(batch_sizes, vec![2, 2, 1]);
+    }
+
+    #[tokio::test]
+    async fn addresses_with_balances_joined() {
+        let daemon = MockDaemon::start(|_, req| match req["method"].as_str() {
+            Some("listaddresses") => MockReply::result(json!(["tb1qaaa", "tb1qbbb"])),
+            _ => match req["params"]["address"].as_str() {
+                Some("tb1qaaa") => {
+                    MockReply::result(json!({"confirmed": "0.1"})).delay(Duration::from_millis(50))
+                }
+                _ => MockReply::result(json!({"confirmed": "0.2", "unconfirmed": "0.01"})),
+            },
+        });
+        let electrum = daemon.electrum();
+
+        let balances = electrum.addresses_with_balances().await.unwrap();
+
+        assert_eq!(balances.len(), 2);
+        assert_eq!(balances[0].0.address, "tb1qaaa");
+        assert_eq!(balances[0].1.confirmed, Decimal::new(1, 1));
+        assert_eq!(balances[1].0.address, "tb1qbbb");
+        assert_eq!(balances[1].1.unconfirmed, Decimal::new(1, 2));
+        assert_eq!(daemon.calls(), 3);
+    }
+
+    #[tokio::test]
+    async fn unused_addresses_distinct() {
+        let daemon = MockDaemon::start(|call, _| {
+            let sequence = ["tb1qaaa", "tb1qbbb", "tb1qbbb", "tb1qccc"];
+            MockReply::result(json!(sequence[call]))
+        });
+        let electrum = daemon.electrum();
+
+        let addresses = electrum.get_unused_addresses(3).await.unwrap();
+
+        let addresses: Vec<&str> = addresses.iter().map(|a| a.address.as_str()).collect();
+        assert_eq!(addresses, vec!["tb1qaaa", "tb1qbbb", "tb1qccc"]);
+        assert_eq!(daemon.calls(), 4);
+    }
+
+    #[tokio::test]
+    async fn unused_addresses_repeated() {
+        let daemon = MockDaemon::start(|_, _| MockReply::result(json!("tb1qaaa")));
+        let electrum = daemon.electrum();
+
+        let res = electrum.get_unused_addresses(3).await;
+
+        assert!(matches!(res, Err(ElectrumRpcError::MalformedResponse(_))));
+        assert_eq!(daemon.calls(), 6);
+    }
+
+    #[tokio::test]
+    async fn pay_to_rejects_sub_satoshi_amount() {
+        let daemon = MockDaemon::start(|_, _| MockReply::result(json!("00")));
+        let electrum = daemon.electrum();
+        let addr = BtcAddress::new("tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn");
+
+        let res = electrum
+            .pay_to(&addr, Decimal::new(1, 9), &PayToOptions::new())
+            .await;
+        assert!(matches!(res, Err(ElectrumRpcError::InvalidParams(_))));
+
+        let res = electrum.add_request(Decimal::new(1, 9), None, None).await;
+        assert!(matches!(res, Err(ElectrumRpcError::InvalidParams(_))));
+        assert_eq!(daemon.calls(), 0);
+    }
+
+    #[tokio::test]
+    async fn wallet_preflight_without_loaded_wallet() {
+        let daemon = MockDaemon::start(|_, _| MockReply::result(json!([])));
+        let electrum = Electrum::builder(LOGIN.clone(), PASSWORD.clone(), daemon.address())
+            .with_wallet_preflight(true)
+            .build()
+            .unwrap();
+        let addr = BtcAddress::new("tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn");
+
+        let res = electrum
+            .pay_to(&addr, Decimal::new(1, 3), &PayToOptions::new())
+            .await;
+        assert!(matches!(
+            res,
+            Err(ElectrumRpcError::ElectrumError(
+                ElectrumErrorCode::WalletNotLoaded
+            ))
+        ));
+
+        let res = electrum.add_request(Decimal::new(1, 3), None, None).await;
+        assert!(matches!(
+            res,
+            Err(ElectrumRpcError::ElectrumError(
+                ElectrumErrorCode::WalletNotLoaded
+            ))
+        ));
+
+        let requests = daemon.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests
+            .iter()
+            .all(|request| request.body["method"] == "list_wallets"));
+    }
+
+    #[cfg(feature = "metrics")]
+    #[tokio::test]
+    async fn metrics_count_bytes() {
+        let daemon = MockDaemon::start(|_, _| MockReply::result(json!({"confirmed": "0.1"})));
+        let electrum = daemon.electrum();
+
+        let addr = BtcAddress::new("tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn");
+
+        electrum.get_address_balance_typed(&addr).await.unwrap();
+        let sent = electrum.metrics().bytes_sent();
+        let received = electrum.metrics().bytes_received();
+        assert!(sent > 0);
+        assert!(received > 0);
+
+        electrum.get_balance().await.unwrap();
+        assert!(electrum.metrics().bytes_sent() > sent);
+        assert!(electrum.metrics().bytes_received() > received);
+    }
+
+    #[tokio::test]
+    async fn retry_after_honored() {
+        let daemon = MockDaemon::start(|call, _| match call {
+            0 => MockReply::result(json!(null))
+                .status(429)
+                .header("retry-after", "1"),
+            _ => MockReply::result(json!({"confirmed": "0.1"})),
+        });
+        let electrum = Electrum::builder(LOGIN.clone(), PASSWORD.clone(), daemon.address())
+            .with_retries(3, Duration::from_millis(10))
+            .build()
+            .unwrap();
+        let addr = BtcAddress::new("tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn");
+
+        let started = std::time::Instant::now();
+        let balance = electrum.get_address_balance_typed(&addr).await.unwrap();
+
+        assert!(started.elapsed() >= Duration::from_secs(1));
+        assert_eq!(balance.confirmed, Decimal::new(1, 1));
+        assert_eq!(daemon.calls(), 2);
+    }
+
+    #[tokio::test]
+    async fn retries_exhausted_rate_limited() {
+        let daemon = MockDaemon::start(|_, _| MockReply::result(json!(null)).status(429));
+        let electrum = Electrum::builder(LOGIN.clone(), PASSWORD.clone(), daemon.address())
+            .with_retries(2, Duration::from_millis(10))
+            .build()
+            .unwrap();
+
+        let res = electrum.get_info().await;
+
+        assert!(matches!(res, Err(ElectrumRpcError::RateLimited)));
+        assert_eq!(daemon.calls(), 3);
+
+        let res = daemon.electrum().get_info().await;
+        assert!(matches!(res, Err(ElectrumRpcError::RateLimited)));
+    }
+
+    #[tokio::test]
+    async fn retry_connection_errors() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio::net::TcpListener;
+
+        // nothing listens on the port until the daemon is back
+        let local_addr = TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let address = format!("http://{}", local_addr);
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            let listener = TcpListener::bind(local_addr).await.unwrap();
+
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = [0; 4096];
+            let _ = stream.read(&mut buf).await.unwrap();
+            let body = r#"{"jsonrpc": "2.0", "result": true, "id": 0}"#;
+            let resp = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            stream.write_all(resp.as_bytes()).await.unwrap();
+        });
+
+        let electrum = Electrum::builder(LOGIN.clone(), PASSWORD.clone(), address)
+            .with_retries(5, Duration::from_millis(20))
+            .build()
+            .unwrap();
+        let addr = BtcAddress::new("tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn");
+
+        assert!(electrum.is_mine(&addr).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn no_retry_once_connected() {
+        use tokio::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        let accepted = Arc::new(AtomicUsize::new(0));
+        let counter = accepted.clone();
+        tokio::spawn(async move {
+            // drop connections unanswered, the request may have been read
+            loop {
+                let connection = listener.accept().await.unwrap();
+                counter.fetch_add(1, Ordering::SeqCst);
+                drop(connection);
+            }
+        });
+
+        let electrum = Electrum::builder(LOGIN.clone(), PASSWORD.clone(), address)
+            .with_retries(3, Duration::from_millis(10))
+            .build()
+            .unwrap();
+
+        let res = electrum.broadcast_typed("0200").await;
+        assert!(matches!(
+            res,
+            Err(ElectrumRpcError::HyperHttpStreamError(_))
+        ));
+        assert_eq!(accepted.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn no_retry_on_rpc_error() {
+        let daemon = MockDaemon::start(|_, _| MockReply::error(-32000, "missing inputs"));
+        let electrum = Electrum::builder(LOGIN.clone(), PASSWORD.clone(), daemon.address())
+            .with_retries(3, Duration::from_millis(10))
+            .build()
+            .unwrap();
+
+        let res = electrum.broadcast_typed("0200").await;
+        assert!(matches!(res, Err(ElectrumRpcError::RpcError { .. })));
+        assert_eq!(daemon.calls(), 1);
+    }
+
+    #[cfg(feature = "secure-memory")]
+    #[tokio::test]
+    async fn secure_memory_credentials() {
+        let daemon = MockDaemon::start(|_, _| MockReply::result(json!(true)));
+        let electrum = daemon.electrum();
+
+        electrum.get_help().await.unwrap();
+
+        let auth = daemon.requests()[0].headers[AUTHORIZATION].clone();
+        let expected = format!("Basic {}", base64::encode("test:test"));
+        assert_eq!(auth, expected.as_str());
+    }
+
+    #[tokio::test]
+    async fn tcp_nodelay_option() {
+        let daemon = MockDaemon::start(|_, _| MockReply::result(json!(true)));
+
+        for enabled in [true, false].iter() {
+            let electrum = Electrum::builder(LOGIN.clone(), PASSWORD.clone(), daemon.address())
+                .with_tcp_nodelay(*enabled)
+                .build()
+                .unwrap();
+
+            let resp = electrum.get_help().await.unwrap();
+            assert_eq!(resp.status(), StatusCode::OK);
+        }
+    }
+
+    #[tokio::test]
+    async fn local_address_bound() {
+        let daemon = MockDaemon::start(|_, _| MockReply::result(json!(true)));
+        let electrum = Electrum::builder(LOGIN.clone(), PASSWORD.clone(), daemon.address())
+            .with_local_address("127.0.0.1".parse().unwrap())
+            .build()
+            .unwrap();
+
+        let resp = electrum.get_help().await.unwrap();
+
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(daemon.calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn http2_prior_knowledge() {
+        let daemon = MockDaemon::start(|_, _| MockReply::result(json!({})));
+        let electrum = Electrum::builder(LOGIN.clone(), PASSWORD.clone(), daemon.address())
+            .with_http2_prior_knowledge(true)
+            .build()
+            .unwrap();
+
+        let res = electrum.get_info_raw().await.unwrap();
+        assert_eq!(res.version(), hyper::Version::HTTP_2);
+    }
+
+    #[tokio::test]
+    async fn parse_result_rpc_error() {
+        let resp = Response::new(Body::from(
+            r#"{"jsonrpc": "2.0", "error": {"code": -32601, "message": "Method not found"}, "id": 7}"#,
+        ));
+
+        let res: Result<String> = parse_result(resp, &json!(7), false).await;
+        match res {
+            Err(ElectrumRpcError::RpcError { code, message, id }) => {
+                assert_eq!(code, -32601);
+                assert_eq!(message, "Method not found");
+                assert_eq!(id, json!(7));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn call_raw_any_command() {
+        let daemon = MockDaemon::start(|_, req| match req["method"].as_str() {
+            Some("getservers") => MockReply::result(json!({"electrum.blockstream.info": {}})),
+            _ => MockReply::error(-32601, "Method not found"),
+        });
+        let electrum = daemon.electrum();
+
+        let servers = electrum.call_raw("getservers", Value::Null).await.unwrap();
+        assert!(servers["electrum.blockstream.info"].is_object());
+
+        let res = electrum
+            .call_raw("getmerkle", json!(["f".repeat(64), 100]))
+            .await;
+        assert!(matches!(
+            res,
+            Err(ElectrumRpcError::RpcError { code: -32601, .. })
+        ));
+
+        let requests = daemon.requests();
+        assert_eq!(requests[0].body["params"], json!({}));
+        assert_eq!(requests[1].body["method"], "getmerkle");
+        assert_eq!(requests[1].body["params"][1], 100);
+        assert_eq!(requests[1].body["id"], 1);
+
+        let res = electrum.call_raw("getmerkle", json!("txid")).await;
+        assert!(matches!(res, Err(ElectrumRpcError::InvalidParams(_))));
+        assert_eq!(daemon.calls(), 2);
+    }
+
+    #[tokio::test]
+    async fn dust_outputs_rejected() {
+        let daemon = MockDaemon::start(|_, _| MockReply::result(json!("0200")));
+        let electrum = Electrum::builder(LOGIN.clone(), PASSWORD.clone(), daemon.address())
+            .with_dust_check(true)
+            .build()
+            .unwrap();
+        let addr = BtcAddress::new("tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn");
+
+        let res = electrum
+            .pay_to(&addr, Decimal::new(293, 8), &PayToOptions::new())
+            .await;
+        assert!(matches!(
+            res,
+            Err(ElectrumRpcError::ElectrumError(
+                ElectrumErrorCode::DustOutput
+            ))
+        ));
+
+        let outputs = vec![
+            Output::from((
+                "tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn",
+                Decimal::new(1, 3),
+            )),
+            Output::from(("mipcBbFg9gMiCh81Kj8tqqdgoZub1ZJRfn", Decimal::new(545, 8))),
+        ];
+        let res = electrum.pay_to_many(Decimal::new(1, 5), outputs).await;
+        assert!(matches!(
+            res,
+            Err(ElectrumRpcError::ElectrumError(
+                ElectrumErrorCode::DustOutput
+            ))
+        ));
+        let res = electrum
+            .pay_and_confirm(&addr, Decimal::new(293, 8), 1, Duration::from_secs(10))
+            .await;
+        assert!(matches!(
+            res,
+            Err(ElectrumRpcError::PaymentFailed { source, .. })
+                if matches!(*source, ElectrumRpcError::ElectrumError(ElectrumErrorCode::DustOutput))
+        ));
+        assert_eq!(daemon.calls(), 0);
+
+        electrum
+            .pay_to(&addr, Decimal::new(294, 8), &PayToOptions::new())
+            .await
+            .unwrap();
+        assert_eq!(daemon.calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn fee_rate_for_target() {
+        let daemon = MockDaemon::start(|_, _| MockReply::result(json!(12345)));
+        let electrum = daemon.electrum();
+
+        let fee_rate = electrum.get_fee_rate(Some(6)).await.unwrap();
+        assert_eq!(fee_rate, Decimal::from(12345));
+        assert_eq!(
+            daemon.requests()[0].body["params"],
+            json!({"fee_method": "eta", "fee_level": 0.5})
+        );
+    }
+
+    #[tokio::test]
+    async fn fee_rate_without_estimate() {
+        let daemon = MockDaemon::start(|_, _| MockReply::result(json!(null)));
+        let electrum = daemon.electrum();
+
+        let res = electrum.get_fee_rate(Some(1)).await;
+        assert!(matches!(res, Err(ElectrumRpcError::NoFeeEstimate)));
+        assert_eq!(daemon.requests()[0].body["params"]["fee_level"], 1.0);
+    }
+
+    #[test]
+    fn eta_fee_levels() {
+        assert_eq!(eta_fee_level(100), 0.0);
+        assert_eq!(eta_fee_level(10), 0.25);
+        assert_eq!(eta_fee_level(3), 0.75);
+        assert_eq!(eta_fee_level(1), 1.0);
+    }
+
+    #[tokio::test]
+    async fn suggested_fee_rate_in_sat_per_vb() {
+        let daemon = MockDaemon::start(|_, _| MockReply::result(json!(2500)));
+        let electrum = daemon.electrum();
+
+        let fee_rate = electrum.suggested_fee_rate().await.unwrap();
+        assert_eq!(fee_rate.as_sat_per_vb(), Decimal::new(25, 1));
+    }
+
+    /// Mock of a wallet that synced pending transaction `TX`, spending
+    /// output 0 of `PREVIOUS_TXID`: like the daemon, it rejects `payto` from
+    /// the coins of `TX` as they are spent already.
+    fn replaceable_tx_daemon(nsequence: u32) -> MockDaemon {
+        use crate::ext::fixtures;
+
+        const PREVIOUS_TXID: &str =
+            "eaf3e55e521431bcd18294b8a536a966f5fbdc9f7701cdbaa877cec9e95b8cb5";
+        const PREVIOUS: &str = "0200";
+
+        MockDaemon::start(move |_, req| match req["method"].as_str() {
+            Some("get_tx_status") => MockReply::result(json!({"confirmations": 0})),
+            Some("gettransaction") if req["params"]["txid"] == PREVIOUS_TXID => {
+                MockReply::result(json!(PREVIOUS))
+            }
+            Some("gettransaction") => MockReply::result(json!(TX)),
+            Some("deserialize") if req["params"]["tx"] == PREVIOUS => MockReply::result(json!({
+                "version": 2,
+                "locktime": 0,
+                "inputs": [],
+                "outputs": [{"scriptpubkey": "0014", "value_sats": 10082}],
+            })),
+            Some("deserialize") => {
+                let mut json: Value = serde_json::from_str(fixtures::DESERIALIZE).unwrap();
+                json["result"]["inputs"][0]["nsequence"] = json!(nsequence);
+                MockReply::result(json["result"].take())
+            }
+            Some("payto") => MockReply::error(1, "Insufficient funds"),
+            Some("getunusedaddress") => {
+                MockReply::result(json!("tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn"))
+            }
+            Some("serialize") => MockReply::result(json!("cHNidP8BAFICAAAAAQ==")),
+            Some("signtransaction") => MockReply::result(json!(TX)),
+            Some("broadcast") => MockReply::result(json!("cc")),
+            _ => MockReply::result(json!(true)),
+        })
+    }
+
+    /// Signed transaction of 141 vB, spending 10082 sat to 9800 sat of outputs.
+    const TX: &str = "02000000000101b58c5be9c9ce77a8bacd01779fdcfbf566a936a5b89482d1bc3114525ee5f3ea0000000000fdffffff02e8030000000000001600149e08b7dbcd1aad00ec6888a60e56c779f0f2da276022000000000000160014d272035ef819d6311231c06014aed5cfb100009e0247304402203db69d69b3fa76050b6c3276bc21bb834996f2c84c31c17c813beba01079705002202d864669f12db9939ea78a45e4c4a982cca68304fef25f334bd6cbbc9971bc9b012103815054ce939185772574ef569fe31b601d5bad48f48d5edaef194cded838c31ac40f1e00";
+
+    #[tokio::test]
+    async fn cancel_transaction_double_spends_inputs() {
+        let daemon = replaceable_tx_daemon(0xffff_fffd);
+
+        let txid = daemon
+            .electrum()
+            .cancel_transaction("dd", Decimal::new(25, 0))
+            .await
+            .unwrap();
+        assert_eq!(txid, "cc");
+
+        let requests = daemon.requests();
+        let methods: Vec<_> = requests.iter().map(|r| r.body["method"].clone()).collect();
+        assert!(!methods.contains(&json!("payto")));
+        assert!(!methods.contains(&json!("createnewaddress")));
+
+        let serialized: Vec<_> = requests
+            .iter()
+            .filter(|req| req.body["method"] == "serialize")
+            .map(|req| req.body["params"]["jsontx"].clone())
+            .collect();
+        assert_eq!(serialized.len(), 2);
+        assert_eq!(
+            serialized[1]["inputs"],
+            json!([{
+                "prevout_hash": "eaf3e55e521431bcd18294b8a536a966f5fbdc9f7701cdbaa877cec9e95b8cb5",
+                "prevout_n": 0,
+                "value_sats": 10082,
+                "nsequence": 0xffff_fffd_u32,
+            }])
+        );
+        // 25 sat/vB over 141 + 1 vB
+        assert_eq!(
+            serialized[1]["outputs"],
+            json!([{"address": "tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn", "value_sats": 10082 - 3550}])
+        );
+        assert_eq!(serialized[1]["locktime"], 1970116);
+
+        let broadcast = requests.last().unwrap();
+        assert_eq!(broadcast.body["method"], "broadcast");
+        assert_eq!(broadcast.body["params"]["tx"], TX);
+    }
+
+    #[tokio::test]
+    async fn cancel_transaction_requires_rbf() {
+        let daemon = replaceable_tx_daemon(0xffff_ffff);
+
+        let res = daemon
+            .electrum()
+            .cancel_transaction("dd", Decimal::new(25, 0))
+            .await;
+        assert!(matches!(res, Err(ElectrumRpcError::InvalidParams(_))));
+        assert!(!daemon
+            .requests()
+            .iter()
+            .any(|req| req.body["method"] == "broadcast"));
+    }
+
+    #[tokio::test]
+    async fn cancel_transaction_fee_too_low() {
+        let daemon = replaceable_tx_daemon(0xffff_fffd);
+
+        // 2 sat/vB pays 284 sat, less than the 282 sat of the original plus 142 sat
+        let res = daemon
+            .electrum()
+            .cancel_transaction("dd", Decimal::new(2, 0))
+            .await;
+        assert!(matches!(res, Err(ElectrumRpcError::InvalidParams(_))));
+    }
+
+    #[tokio::test]
+    async fn cancel_confirmed_transaction() {
+        let daemon = MockDaemon::start(|_, _| MockReply::result(json!({"confirmations": 2})));
+        let electrum = daemon.electrum();
+
+        let res = electrum.cancel_transaction("dd", Decimal::new(25, 0)).await;
+        assert!(matches!(
+            res,
+            Err(ElectrumRpcError::ElectrumError(
+                ElectrumErrorCode::AlreadyConfirmed
+            ))
+        ));
+        assert_eq!(daemon.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn call_timeout() {
+        let daemon = MockDaemon::start(|_, _| {
+            MockReply::result(json!({})).delay(Duration::from_millis(500))
+        });
+        let electrum = Electrum::builder(LOGIN.clone(), PASSWORD.clone(), daemon.address())
+            .with_timeout(Duration::from_millis(50))
+            .build()
+            .unwrap();
+
+        let res = electrum.get_info_raw().await;
+        assert!(matches!(res, Err(ElectrumRpcError::Timeout)));
+
+        let electrum = daemon.electrum();
+        assert!(electrum.get_info_raw().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn call_timeout_covers_body() {
+        let daemon = MockDaemon::start(|_, _| {
+            MockReply::result(json!({})).body_delay(Duration::from_millis(500))
+        });
+        let electrum = Electrum::builder(LOGIN.clone(), PASSWORD.clone(), daemon.address())
+            .with_timeout(Duration::from_millis(50))
+            .build()
+            .unwrap();
+
+        let res = electrum.get_tx_status("aa").await;
+        assert!(matches!(res, Err(ElectrumRpcError::Timeout)));
+    }
+
+    #[tokio::test]
+    async fn broadcast_and_verify_accepted() {
+        let daemon = MockDaemon::start(|_, req| match req["method"].as_str() {
+            Some("broadcast") => MockReply::result(json!("aa")),
+            _ => MockReply::result(json!({"confirmations": 0})),
+        });
+        let electrum = daemon.electrum();
+
+        let txid = electrum.broadcast_and_verify("0200").await.unwrap();
+        assert_eq!(txid, "aa");
+        assert_eq!(daemon.requests()[1].body["params"]["txid"], "aa");
+    }
+
+    #[tokio::test]
+    async fn broadcast_and_verify_rejected() {
+        let daemon = MockDaemon::start(|_, req| match req["method"].as_str() {
+            Some("broadcast") => MockReply::result(json!("aa")),
+            _ => MockReply::error(1, "Transaction not in wallet."),
+        });
+        let electrum = daemon.electrum();
+
+        let res = electrum.broadcast_and_verify("0200").await;
+        assert!(matches!(res, Err(ElectrumRpcError::NotInMempool(txid)) if txid == "aa"));
+    }
+
+    #[tokio::test]
+    async fn broadcast_and_verify_passes_other_errors() {
+        let daemon = MockDaemon::start(|_, req| match req["method"].as_str() {
+            Some("broadcast") => MockReply::result(json!("aa")),
+            _ => MockReply::error(-32603, "wallet not loaded"),
+        });
+        let electrum = daemon.electrum();
+
+        let res = electrum.broadcast_and_verify("0200").await;
+        assert!(matches!(
+            res,
+            Err(ElectrumRpcError::RpcError { code: -32603, message, .. }) if message == "wallet not loaded"
+        ));
+    }
+
+    #[cfg(not(feature = "tls"))]
+    #[test]
+    fn https_requires_tls_feature() {
+        let res = Electrum::new(
+            LOGIN.clone(),
+            PASSWORD.clone(),
+            "https://127.0.0.1:7000".to_string(),
+        );
+        assert!(matches!(res, Err(ElectrumRpcError::InvalidParams(_))));
+    }
+
+    #[cfg(feature = "tls")]
+    #[tokio::test]
+    async fn https_or_http_with_tls_feature() {
+        Electrum::new(
+            LOGIN.clone(),
+            PASSWORD.clone(),
+            "https://127.0.0.1:7000".to_string(),
+        )
+        .unwrap();
+
+        let daemon = MockDaemon::start(|_, _| MockReply::result(json!({})));
+        assert!(daemon.electrum().get_info_raw().await.is_ok());
+    }
+
+    #[cfg(feature = "tls")]
+    #[test]
+    fn pinned_cert_requires_https() {
+        let res = Electrum::builder(
+            LOGIN.clone(),
+            PASSWORD.clone(),
+            "http://127.0.0.1:7000".to_string(),
+        )
+        .with_pinned_cert([0; 32])
+        .build();
+        assert!(matches!(res, Err(ElectrumRpcError::InvalidParams(_))));
+
+        Electrum::builder(
+            LOGIN.clone(),
+            PASSWORD.clone(),
+            "https://127.0.0.1:7000".to_string(),
+        )
+        .with_pinned_cert([0; 32])
+        .build()
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn get_unused_address_owned() {
+        let daemon = MockDaemon::start(|_, _| {
+            MockReply::result(json!("tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn"))
+        });
+        let electrum = daemon.electrum();
+
+        let address = electrum.get_unused_address().await.unwrap();
+        assert_eq!(
+            address.as_btc_address().address,
+            "tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn"
+        );
+        assert_eq!(daemon.requests()[0].body["method"], "getunusedaddress");
+    }
+
+    #[tokio::test]
+    async fn strict_parsing_rejects_unknown_fields() {
+        let reply = || json!({"confirmations": 3, "mined_at": 1_600_000_000});
+        let daemon = MockDaemon::start(move |_, _| MockReply::result(reply()));
+
+        let status = daemon.electrum().get_tx_status("aa").await.unwrap();
+        assert_eq!(status.confirmations, 3);
+
+        let electrum = Electrum::builder(LOGIN.clone(), PASSWORD.clone(), daemon.address())
+            .with_strict_parsing(true)
+            .build()
+            .unwrap();
+        let res = electrum.get_tx_status("aa").await;
+        assert!(
+            matches!(res, Err(ElectrumRpcError::JsonError(e)) if e.to_string().contains("mined_at"))
+        );
+    }
+
+    #[tokio::test]
+    async fn strict_parsing_spares_internal_helpers() {
+        use crate::ext::fixtures;
+
+        let daemon = MockDaemon::start(|_, req| match req["method"].as_str() {
+            Some("onchain_history") => MockReply::raw(fixtures::ONCHAIN_HISTORY),
+            _ => MockReply::result(json!({
+                "address": "tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn",
+                "amount_BTC": "0.001",
+                "status": 3,
+            })),
+        });
+        let electrum = Electrum::builder(LOGIN.clone(), PASSWORD.clone(), daemon.address())
+            .with_strict_parsing(true)
+            .build()
+            .unwrap();
+        let addr = BtcAddress::new("tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn");
+
+        assert_eq!(electrum.transaction_report().await.unwrap().len(), 3);
+        electrum
+            .wait_for_payment(
+                &addr,
+                &PollConfig::new(Duration::from_millis(10), Duration::from_millis(50)),
+                None,
+            )
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn sign_then_verify_message() {
+        let daemon = MockDaemon::start(|_, req| {
+            match req["method"].as_str() {
+            Some("signmessage") => MockReply::result(json!("H9L5yLFjti0QTHhPyFrZCT1V/MMnBtXKmoiKDZ78NDBjERki6ZTQZdSMCtkgoNmp17By9ItJr8o7ChX0XxY91nk=")),
+            Some("verifymessage") => MockReply::result(json!(
+                req["params"]["signature"] == "H9L5yLFjti0QTHhPyFrZCT1V/MMnBtXKmoiKDZ78NDBjERki6ZTQZdSMCtkgoNmp17By9ItJr8o7ChX0XxY91nk="
+                    && req["params"]["message"] == "hello"
+            )),
+            _ => MockReply::error(-32601, "Method not found"),
+        }
+        });
+        let electrum = daemon.electrum();
+        let address = BtcAddress::new("tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn");
+
+        let signature = electrum.sign_message(&address, "hello").await.unwrap();
+        assert!(electrum
+            .verify_message(&address, &signature, "hello")
+            .await
+            .unwrap());
+        assert!(!electrum
+            .verify_message(&address, &signature, "tampered")
+            .await
+            .unwrap());
+        assert_eq!(
+            daemon.requests()[1].body["params"]["address"],
+            "tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn"
+        );
+    }
+
+    #[tokio::test]
+    async fn mempool_fee_rate() {
+        use crate::ext::fixtures;
+
+        const TX: &str = "02000000000101b58c5be9c9ce77a8bacd01779fdcfbf566a936a5b89482d1bc3114525ee5f3ea0000000000fdffffff02e8030000000000001600149e08b7dbcd1aad00ec6888a60e56c779f0f2da276022000000000000160014d272035ef819d6311231c06014aed5cfb100009e0247304402203db69d69b3fa76050b6c3276bc21bb834996f2c84c31c17c813beba01079705002202d864669f12db9939ea78a45e4c4a982cca68304fef25f334bd6cbbc9971bc9b012103815054ce939185772574ef569fe31b601d5bad48f48d5edaef194cded838c31ac40f1e00";
+
+        const PREVIOUS_TXID: &str =
+            "eaf3e55e521431bcd18294b8a536a966f5fbdc9f7701cdbaa877cec9e95b8cb5";
+        const PREVIOUS: &str = "0200";
+
+        let daemon = MockDaemon::start(|_, req| match req["method"].as_str() {
+            Some("get_tx_status") => MockReply::result(json!({"confirmations": 0})),
+            Some("gettransaction") if req["params"]["txid"] == PREVIOUS_TXID => {
+                MockReply::result(json!(PREVIOUS))
+            }
+            Some("gettransaction") => MockReply::result(json!(TX)),
+            Some("deserialize") if req["params"]["tx"] == PREVIOUS => MockReply::result(json!({
+                "version": 2,
+                "locktime": 0,
+                "inputs": [],
+                "outputs": [{"scriptpubkey": "0014", "value_sats": 10082}],
+            })),
+            _ => MockReply::raw(fixtures::DESERIALIZE),
+        });
+        let electrum = daemon.electrum();
+
+        // 10082 sat spent, 9800 sent: 282 sat over 141 vB
+        let rate = electrum
+            .get_mempool_fee_rate(
+                "9a1c2b3d4e5f60718293a4b5c6d7e8f90a1b2c3d4e5f60718293a4b5c6d7e8f9",
+            )
+            .await
+            .unwrap();
+        assert_eq!(rate, Some(Decimal::from(2)));
+    }
+
+    #[tokio::test]
+    async fn mempool_fee_rate_confirmed() {
+        let daemon = MockDaemon::start(|_, _| MockReply::result(json!({"confirmations": 3})));
+        let electrum = daemon.electrum();
+
+        let rate = electrum
+            .get_mempool_fee_rate(
+                "eaf3e55e521431bcd18294b8a536a966f5fbdc9f7701cdbaa877cec9e95b8cb5",
+            )
+            .await
+            .unwrap();
+        assert_eq!(rate, None);
+        assert_eq!(daemon.calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn get_transaction_hex() {
+        let daemon = MockDaemon::start(|_, _| MockReply::result(json!("0200")));
+        let electrum = daemon.electrum();
+
+        let hex = electrum
+            .get_transaction("eaf3e55e521431bcd18294b8a536a966f5fbdc9f7701cdbaa877cec9e95b8cb5")
+            .await
+            .unwrap();
+        assert_eq!(hex, "0200");
+        assert_eq!(
+            daemon.requests()[0].body["params"]["txid"],
+            "eaf3e55e521431bcd18294b8a536a966f5fbdc9f7701cdbaa877cec9e95b8cb5"
+        );
+
+        let res = electrum.get_transaction("not-a-txid").await;
+        assert!(matches!(res, Err(ElectrumRpcError::InvalidTxid(_))));
+        assert_eq!(daemon.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn generated_string_ids() {
+        let daemon = MockDaemon::start(|_, _| MockReply::result(json!(true)));
+        let counter = Arc::new(AtomicUsize::new(0));
+        let electrum = Electrum::builder(LOGIN.clone(), PASSWORD.clone(), daemon.address())
+            .with_id_generator(move || {
+                let n = counter.fetch_add(1, Ordering::SeqCst);
+                json!(format!("9b2e4c1a-7f3d-4e6b-8a5c-{:012x}", n))
+            })
+            .build()
+            .unwrap();
+
+        let address = BtcAddress::new("tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn");
+        assert!(electrum.is_mine(&address).await.unwrap());
+        assert!(electrum.is_mine(&address).await.unwrap());
+
+        let requests = daemon.requests();
+        assert_eq!(
+            requests[0].body["id"],
+            "9b2e4c1a-7f3d-4e6b-8a5c-000000000000"
+        );
+        assert_eq!(
+            requests[1].body["id"],
+            "9b2e4c1a-7f3d-4e6b-8a5c-000000000001"
+        );
+    }
+
+    #[tokio::test]
+    async fn html_error_page() {
+        let page = "\n<!DOCTYPE html><html><body><h1>502 Bad Gateway</h1></body></html>";
+        let daemon = MockDaemon::start(move |_, _| MockReply::raw(page));
+
+        let res = daemon.electrum().get_wallet_info().await;
+        assert!(
+            matches!(&res, Err(ElectrumRpcError::MalformedResponse(hint)) if hint.contains("proxy"))
+        );
+
+        let daemon = MockDaemon::start(|_, _| {
+            MockReply::raw("Bad Gateway").header("content-type", "text/html; charset=utf-8")
+        });
+        let res = daemon.electrum().get_wallet_info().await;
+        assert!(matches!(res, Err(ElectrumRpcError::MalformedResponse(_))));
+    }
+
+    #[tokio::test]
+    async fn pay_to_network_mismatch() {
+        let daemon = MockDaemon::start(|_, _| MockReply::result(json!("0200")));
+        let electrum = Electrum::builder(LOGIN.clone(), PASSWORD.clone(), daemon.address())
+            .with_network(Network::Testnet)
+            .build()
+            .unwrap();
+
+        let mainnet = BtcAddress::new("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4");
+        let res = electrum
+            .pay_to(&mainnet, Decimal::new(1, 3), &PayToOptions::new())
+            .await;
+        assert!(matches!(
+            res,
+            Err(ElectrumRpcError::NetworkMismatch {
+                network: Network::Testnet,
+                ..
+            })
+        ));
+        let res = electrum
+            .pay_to_many(
+                Decimal::new(1, 5),
+                vec![(&mainnet, Decimal::new(1, 3)).into()],
+            )
+            .await;
+        assert!(matches!(res, Err(ElectrumRpcError::NetworkMismatch { .. })));
+        let res = electrum
+            .pay_and_confirm(&mainnet, Decimal::new(1, 3), 1, Duration::from_secs(10))
+            .await;
+        assert!(matches!(
+            res,
+            Err(ElectrumRpcError::PaymentFailed {
+                stage: PaymentStage::Create,
+                source,
+                ..
+            }) if matches!(*source, ElectrumRpcError::NetworkMismatch { .. })
+        ));
+        assert_eq!(daemon.calls(), 0);
+
+        let testnet = BtcAddress::new("tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn");
+        electrum
+            .pay_to(&testnet, Decimal::new(1, 3), &PayToOptions::new())
+            .await
+            .unwrap();
+        assert_eq!(daemon.calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn sign_psbt_typed_incomplete() {
+        use crate::ext::fixtures;
+
+        let daemon = MockDaemon::start(|_, _| MockReply::raw(fixtures::SIGN_PSBT_INCOMPLETE));
+        let electrum = daemon.electrum();
+
+        let signed = electrum
+            .sign_psbt_typed("cHNidP8BAFICAAAAAQ==", Some("secret"))
+            .await
+            .unwrap();
+        assert!(!signed.complete);
+        assert_eq!(daemon.requests()[0].body["params"]["password"], "secret");
+    }
+
+    #[tokio::test]
+    async fn list_addresses_typed_options() {
+        use crate::ext::fixtures;
+
+        let daemon = MockDaemon::start(|_, _| MockReply::raw(fixtures::LIST_ADDRESSES_DETAILED));
+        let options = ListAddressesOptions {
+            balance: true,
+            labels: true,
+        };
+
+        let entries = daemon
+            .electrum()
+            .list_addresses_typed(&options)
+            .await
+            .unwrap();
+        assert_eq!(entries[0].label.as_deref(), Some("savings"));
+        assert_eq!(entries[0].balance, Some(Decimal::new(1, 3)));
+        assert_eq!(
+            daemon.requests()[0].body["params"],
+            json!({"balance": true, "labels": true})
+        );
+    }
+
+    #[tokio::test]
+    async fn add_transaction() {
+        let daemon = MockDaemon::start(|_, _| MockReply::result(json!("aa")));
+
+        assert_eq!(
+            daemon.electrum().add_transaction("0200").await.unwrap(),
+            "aa"
+        );
+
+        let requests = daemon.requests();
+        assert_eq!(requests[0].body["method"], "addtransaction");
+        assert_eq!(requests[0].body["params"], json!({"tx": "0200"}));
+
+        let daemon = MockDaemon::start(|_, _| MockReply::result(json!(false)));
+        let res = daemon.electrum().add_transaction("0200").await;
+        assert!(matches!(
+            res,
+            Err(ElectrumRpcError::ElectrumError(
+                ElectrumErrorCode::TransactionNotAdded
+            ))
+        ));
+    }
+
+    #[tokio::test]
+    async fn freeze_and_unfreeze_address() {
+        let daemon = MockDaemon::start(|_, _| MockReply::result(json!(true)));
+        let electrum = daemon.electrum();
+        let address = BtcAddress::new("tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn");
+
+        assert!(electrum.freeze(&address).await.unwrap());
+        assert!(electrum.unfreeze(&address).await.unwrap());
+
+        let requests = daemon.requests();
+        assert_eq!(requests[0].body["method"], "freeze");
+        assert_eq!(requests[1].body["method"], "unfreeze");
+        for request in &requests {
+            assert_eq!(
+                request.body["params"],
+                json!({"address": "tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn"})
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn list_unspent_typed() {
+        use crate::ext::fixtures;
+
+        let daemon = MockDaemon::start(|_, _| MockReply::raw(fixtures::LIST_UNSPENT));
+        let electrum = daemon.electrum();
+
+        let utxos = electrum.list_unspent().await.unwrap();
+        assert_eq!(utxos.len(), 2);
+        assert_eq!(
+            utxos[0].address.address,
+            "tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn"
+        );
+        assert_eq!(utxos[0].value, Decimal::new(1, 3));
+        assert_eq!(
+            utxos[0].prevout_hash,
+            "eaf3e55e521431bcd18294b8a536a966f5fbdc9f7701cdbaa877cec9e95b8cb5"
+        );
+        assert_eq!(utxos[0].prevout_n, 0);
+        assert_eq!(utxos[0].height, 1936900);
+        assert_eq!(utxos[1].value, Decimal::new(512, 6));
+        assert_eq!(utxos[1].prevout_n, 1);
+        assert!(!utxos[1].is_confirmed());
+    }
+
+    #[tokio::test]
+    async fn get_utxo_by_outpoint() {
+        use crate::ext::fixtures;
+
+        let daemon = MockDaemon::start(|_, _| MockReply::raw(fixtures::LIST_UNSPENT));
+        let electrum = daemon.electrum();
+        let hash = "3d10b4ad1ad56e4e1e8ad6bc6fe9bb8d4d0c84d7c7b13e2b8c0a4b9f2a6c1d0e";
+
+        let utxo = electrum.get_utxo(hash, 1).await.unwrap().unwrap();
+        assert_eq!(utxo.value, Decimal::new(512, 6));
+        assert_eq!(electrum.get_utxo(hash, 0).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn confirmed_utxo_value_skips_unconfirmed() {
+        use crate::ext::fixtures;
+
+        let daemon = MockDaemon::start(|_, _| MockReply::raw(fixtures::LIST_UNSPENT));
+        let electrum = daemon.electrum();
+
+        let value = electrum.confirmed_utxo_value().await.unwrap();
+        assert_eq!(value, Decimal::new(1, 3));
+        assert_eq!(daemon.requests()[0].body["method"], "listunspent");
+    }
+
+    #[tokio::test]
+    async fn new_verified_credentials() {
+        let daemon = MockDaemon::start(|_, _| MockReply::result(json!("4.0.9")));
+
+        Electrum::new_verified(LOGIN.clone(), PASSWORD.clone(), daemon.address())
+            .await
+            .unwrap();
+        let request = &daemon.requests()[0];
+        assert_eq!(request.body["method"], "version");
+        assert!(request.headers.contains_key("authorization"));
+
+        let daemon = MockDaemon::start(|_, _| MockReply::raw("").status(401));
+        let res =
+            Electrum::new_verified(LOGIN.clone(), "wrong".to_string(), daemon.address()).await;
+        assert!(matches!(res, Err(ElectrumRpcError::Unauthorized)));
+    }
+
+    #[tokio::test]
+    async fn keepalive_health_flips() {
+        let daemon = MockDaemon::start(|call, _| match call {
+            0 => MockReply::result(json!("4.0.9")),
+            _ => MockReply::result(json!("4.0.9")).delay(Duration::from_secs(5)),
+        });
+        let electrum = Electrum::builder(LOGIN.clone(), PASSWORD.clone(), daemon.address())
+            .with_keepalive(Duration::from_millis(50))
+            .build()
+            .unwrap();
+
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while daemon.calls() < 2 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(daemon.requests()[0].body["method"], "version");
+
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while electrum.is_healthy() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn cloned_client_calls() {
+        let daemon = MockDaemon::start(|_, _| MockReply::result(json!(true)));
+        let electrum = daemon.electrum();
+        let address = BtcAddress::new("tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn");
+
+        let clone = electrum.clone();
+        let mine = tokio::spawn(async move {
+            let address = BtcAddress::new("tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn");
+            clone.is_mine(&address).await
+        });
+        assert!(mine.await.unwrap().unwrap());
+        assert!(electrum.is_mine(&address).await.unwrap());
+
+        let request = &daemon.requests()[0];
+        assert!(request.headers.contains_key(AUTHORIZATION));
+        // the id counter is shared with the clone
+        assert_eq!(daemon.requests()[1].body["id"], 1);
+        assert_eq!(electrum.next_id(), 2);
+    }
+
+    #[tokio::test]
+    async fn address_path_preserved() {
+        let daemon = MockDaemon::start(|_, _| MockReply::result(json!(true)));
+        let address = format!("{}/electrum/rpc", daemon.address());
+        let electrum = Electrum::new(LOGIN.clone(), PASSWORD.clone(), address).unwrap();
+
+        electrum
+            .is_mine(&BtcAddress::new(
+                "tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn",
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(daemon.requests()[0].path, "/electrum/rpc");
+    }
+
+    #[tokio::test]
+    async fn incrementing_request_ids() {
+        let daemon = MockDaemon::start(|_, _| MockReply::result(json!(true)));
+        let electrum = daemon.electrum();
+        let address = BtcAddress::new("tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn");
+
+        assert_eq!(electrum.next_id(), 0);
+        electrum.is_mine(&address).await.unwrap();
+        electrum.is_mine(&address).await.unwrap();
+        assert_eq!(electrum.next_id(), 2);
+
+        let ids: Vec<Value> = daemon
+            .requests()
+            .iter()
+            .map(|req| req.body["id"].clone())
+            .collect();
+        assert_eq!(ids, vec![json!(0), json!(1)]);
+
+        let daemon = MockDaemon::start(|_, _| {
+            MockReply::verbatim(r#"{"jsonrpc":"2.0","result":true,"id":0}"#)
+        });
+        let electrum = daemon.electrum();
+        electrum.is_mine(&address).await.unwrap();
+        let res = electrum.is_mine(&address).await;
+        assert!(matches!(
+            res,
+            Err(ElectrumRpcError::IdMismatch { expected, got }) if expected == 1 && got == 0
+        ));
+    }
+
+    #[tokio::test]
+    async fn get_info_typed() {
+        use crate::ext::fixtures;
+
+        let daemon = MockDaemon::start(|_, _| MockReply::raw(fixtures::GET_INFO));
+        let electrum = daemon.electrum();
+
+        let info = electrum.get_info().await.unwrap();
+        assert_eq!(info.blockchain_height, 1937000);
+        assert_eq!(info.server_height, 1937002);
+        assert!(info.connected);
+        assert_eq!(info.version, "4.0.9");
+        assert_eq!(info.path, PathBuf::from("/home/electrum/.electrum/testnet"));
+        assert_eq!(info.auto_connect, Some(true));
+        assert_eq!(info.server.as_deref(), Some("testnet.aranguren.org"));
+
+        let daemon = MockDaemon::start(|_, _| {
+            MockReply::result(json!({
+                "blockchain_height": 1937000,
+                "server_height": 1937002,
+                "connected": false,
+                "version": "4.0.9",
+                "path": "/home/electrum/.electrum/testnet",
+            }))
+        });
+        let info = daemon.electrum().get_info().await.unwrap();
+        assert_eq!(info.auto_connect, None);
+        assert_eq!(info.server, None);
+
+        let daemon = MockDaemon::start(|_, _| MockReply::error(-32603, "internal error"));
+        let electrum = daemon.electrum();
+        assert!(electrum.get_info().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn estimate_confirmation_blocks_from_getinfo() {
+        use crate::ext::fixtures;
+
+        let mut info: Value = serde_json::from_str(fixtures::GET_INFO).unwrap();
+        info["result"]["fee_estimates"] = json!({"2": 20000, "5": 10000, "25": 1000});
+        let daemon = MockDaemon::start(move |_, _| MockReply::result(info["result"].clone()));
+        let electrum = daemon.electrum();
+
+        let blocks = electrum
+            .estimate_confirmation_blocks(Decimal::new(12, 0))
+            .await
+            .unwrap();
+        assert_eq!(blocks, Some(5));
+
+        let blocks = electrum
+            .estimate_confirmation_blocks(Decimal::new(5, 1))
+            .await
+            .unwrap();
+        assert_eq!(blocks, None);
+    }
+
+    #[tokio::test]
+    async fn request_too_large() {
+        let daemon = MockDaemon::start(|_, _| MockReply::raw("").status(413));
+        let electrum = daemon.electrum();
+
+        let outputs = vec![(
+            "tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn",
+            Decimal::new(1, 5),
+        )
+            .into()];
+        let res = electrum.pay_to_many(Decimal::new(1, 5), outputs).await;
+        assert!(matches!(res, Err(ElectrumRpcError::RequestTooLarge)));
+    }
+
+    #[tokio::test]
+    async fn spendable_balance_modes() {
+        let daemon = MockDaemon::start(|_, _| {
+            MockReply::result(json!({"confirmed": "0.5", "unconfirmed": "0.25"}))
+        });
+        let electrum = daemon.electrum();
+
+        let confirmed = electrum.spendable_balance(false).await.unwrap();
+        assert_eq!(confirmed, Decimal::new(5, 1));
+
+        let with_unconfirmed = electrum.spendable_balance(true).await.unwrap();
+        assert_eq!(with_unconfirmed, Decimal::new(75, 2));
+    }
+
+    #[tokio::test]
+    async fn parse_result_fixtures() {
+        use crate::ext::fixtures;
+
+        let balance: Balance = parse_result(
+            Response::new(Body::from(fixtures::BALANCE)),
+            &json!(0),
+            false,
+        )
+        .await
+        .unwrap();
+        assert_eq!(balance.confirmed, Decimal::new(1512, 6));
+
+        let history: Vec<HistoryEntry> = parse_result(
+            Response::new(Body::from(fixtures::ADDRESS_HISTORY)),
+            &json!(0),
+            false,
+        )
+        .await
+        .unwrap();
+        assert_eq!(history.len(), 2);
+        assert!(!history[0].is_confirmed());
+
+        let proof: MerkleProof = parse_result(
+            Response::new(Body::from(fixtures::MERKLE)),
+            &json!(0),
+            false,
+        )
+        .await
+        .unwrap();
+        assert_eq!(proof.block_height, 1936900);
+        assert_eq!(proof.merkle.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn parse_result_id_mismatch() {
+        let resp = Response::new(Body::from(r#"{"jsonrpc":"2.0","result":true,"id":7}"#));
 
-    use super::*;
+        let res: Result<bool> = parse_result(resp, &json!(3), false).await;
+        match res {
+            Err(ElectrumRpcError::IdMismatch { expected, got }) => {
+                assert_eq!(expected, 3);
+                assert_eq!(got, 7);
+            }
+            other => panic!("unexpected {:?}", other.map(|_| ())),
+        }
+
+        let daemon = MockDaemon::start(|_, _| {
+            MockReply::verbatim(r#"{"jsonrpc":"2.0","result":{},"id":42}"#)
+        });
+        let res = daemon.electrum().get_wallet_info().await;
+        assert!(matches!(
+            res,
+            Err(ElectrumRpcError::IdMismatch { expected, .. }) if expected == 0
+        ));
+    }
+
+    #[tokio::test]
+    async fn parse_result_error_null_id() {
+        let resp = Response::new(Body::from(
+            r#"{"jsonrpc":"2.0","error":{"code":-32700,"message":"Parse error"},"id":null}"#,
+        ));
+
+        let res: Result<bool> = parse_result(resp, &json!(3), false).await;
+        match res {
+            Err(ElectrumRpcError::RpcError { code, message, .. }) => {
+                assert_eq!(code, -32700);
+                assert_eq!(message, "Parse error");
+            }
+            other => panic!("unexpected {:?}", other.map(|_| ())),
+        }
+
+        let resp = Response::new(Body::from(r#"{"jsonrpc":"2.0","result":true,"id":null}"#));
+        let res: Result<bool> = parse_result(resp, &json!(3), false).await;
+        assert!(matches!(res, Err(ElectrumRpcError::IdMismatch { .. })));
+    }
+
+    #[tokio::test]
+    async fn parse_result_already_confirmed() {
+        use crate::ext::fixtures;
+
+        let resp = Response::new(Body::from(fixtures::BROADCAST_ALREADY_CONFIRMED));
+
+        let res: Result<String> = parse_result(resp, &json!(0), false).await;
+        assert!(matches!(
+            res,
+            Err(ElectrumRpcError::ElectrumError(
+                ElectrumErrorCode::AlreadyConfirmed
+            ))
+        ));
+    }
+
+    #[tokio::test]
+    async fn server_info_fixture() {
+        use crate::ext::fixtures;
+
+        let daemon = MockDaemon::start(|_, _| MockReply::raw(fixtures::GET_INFO));
+        let electrum = daemon.electrum();
+
+        let (server, protocol_version) = electrum.server_info().await.unwrap();
+
+        assert_eq!(server, "testnet.aranguren.org");
+        assert_eq!(protocol_version, "1.4.2");
+    }
+
+    #[tokio::test]
+    async fn sync_lag_fixture() {
+        use crate::ext::fixtures;
+
+        let daemon = MockDaemon::start(|_, _| MockReply::raw(fixtures::GET_INFO));
+
+        let lag = daemon.electrum().sync_lag().await.unwrap();
+        assert_eq!(lag, 2);
+    }
+
+    #[tokio::test]
+    async fn has_lightning_fixtures() {
+        use crate::ext::fixtures;
+
+        let daemon = MockDaemon::start(|_, _| MockReply::raw(fixtures::GET_INFO_LIGHTNING));
+        assert!(daemon.electrum().has_lightning().await.unwrap());
+        assert_eq!(daemon.calls(), 1);
+
+        let daemon = MockDaemon::start(|_, req| match req["method"].as_str() {
+            Some("getinfo") => MockReply::raw(fixtures::GET_INFO),
+            _ => MockReply::result(json!(["getinfo", "help", "payto", "broadcast"])),
+        });
+        assert!(!daemon.electrum().has_lightning().await.unwrap());
+        assert_eq!(daemon.requests()[1].body["method"], "help");
+    }
+
+    #[cfg(feature = "lightning")]
+    #[tokio::test]
+    async fn list_lightning_invoices_fixture() {
+        use crate::ext::fixtures;
+
+        let daemon = MockDaemon::start(|_, _| MockReply::raw(fixtures::LIST_REQUESTS_LIGHTNING));
+
+        let invoices = daemon.electrum().list_lightning_invoices().await.unwrap();
+
+        assert_eq!(invoices.len(), 2);
+        assert!(invoices[0].bolt11.starts_with("lntb2500u1"));
+        assert_eq!(invoices[0].amount, Some(Decimal::new(25, 4)));
+        assert!(invoices[0].is_paid());
+        assert_eq!(invoices[1].amount, None);
+        assert_eq!(invoices[1].status, 1);
+        assert_eq!(
+            invoices[1].payment_hash,
+            "0001020304050607080900010203040506070809000102030405060708090102"
+        );
+    }
+
+    #[tokio::test]
+    async fn get_wallet_info_fixture() {
+        use crate::ext::fixtures;
+
+        let daemon = MockDaemon::start(|_, _| MockReply::raw(fixtures::WALLET_INFO));
+        let electrum = daemon.electrum();
+
+        let info = electrum.get_wallet_info().await.unwrap();
+
+        assert_eq!(daemon.requests()[0].body["method"], "wallet_info");
+        assert_eq!(info.wallet_type, "standard");
+        assert_eq!(info.gap_limit, Some(20));
+        assert!(info.seed_available);
+        assert_eq!(info.keystores.len(), 1);
+        assert_eq!(info.keystores[0].keystore_type, "bip32");
+        assert_eq!(info.keystores[0].derivation.as_deref(), Some("m/84'/1'/0'"));
+
+        let partial: WalletDetails =
+            serde_json::from_value(json!({"wallet_type": "imported"})).unwrap();
+        assert_eq!(partial.gap_limit, None);
+        assert!(partial.keystores.is_empty());
+    }
+
+    #[tokio::test]
+    async fn parse_result_fee_histogram() {
+        let resp = Response::new(Body::from(
+            r#"{"jsonrpc":"2.0","result":[[12.5,50000],[5,120000],[1.01,800000]],"id":0}"#,
+        ));
+
+        let histogram: Vec<(Decimal, u64)> = parse_result(resp, &json!(0), false).await.unwrap();
+        assert_eq!(
+            histogram,
+            vec![
+                (Decimal::new(125, 1), 50000),
+                (Decimal::new(5, 0), 120000),
+                (Decimal::new(101, 2), 800000),
+            ]
+        );
+    }
 
     #[test]
-    fn new_electrum_instance0() {
+    fn rpc_body_payment_request() {
+        let body = PaymentRequest::new(Decimal::new(1, 3))
+            .memo("coffee")
+            .expiration(3600)
+            .to_body();
+
+        let actual = serde_json::to_value(&body).unwrap();
+        assert_eq!(actual["method"], "add_request");
+        assert_eq!(
+            actual["params"],
+            json!({"amount": 0.001, "memo": "coffee", "expiration": 3600})
+        );
+
+        let body = PaymentRequest::new(Decimal::new(1, 3)).to_body();
+        let actual = serde_json::to_value(&body).unwrap();
+        assert_eq!(
+            actual["params"],
+            json!({"amount": 0.001, "expiration": ELECTRUM_DEFAULT_EXPIRATION})
+        );
+    }
+
+    #[test]
+    fn rpc_body_pay_to_data() {
+        let body = pay_to_data_body("DEADBEEF", Some(Decimal::new(1, 5)))
+            .unwrap()
+            .build();
+
+        let actual = serde_json::to_value(&body).unwrap();
+        assert_eq!(actual["method"], "payto");
+        assert_eq!(
+            actual["params"],
+            json!({
+                "destination": "script(OP_RETURN deadbeef)",
+                "amount": "0",
+                "fee": "0.00001",
+            })
+        );
+
+        let res = pay_to_data_body(&"00".repeat(81), None);
+        assert!(matches!(res, Err(ElectrumRpcError::InvalidParams(_))));
+        let res = pay_to_data_body("xyz", None);
+        assert!(matches!(res, Err(ElectrumRpcError::InvalidParams(_))));
+    }
+
+    #[test]
+    fn rpc_body_pay_to() {
+        let addr = BtcAddress::new("tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn");
+        let body = PayToOptions::new()
+            .to_builder(
+                &addr,
+                Amount::from_btc_checked(Decimal::new(15, 4)).unwrap(),
+            )
+            .build();
+
+        let actual = serde_json::to_value(&body).unwrap();
+        assert_eq!(actual["method"], "payto");
+        assert_eq!(
+            actual["params"],
+            json!({
+                "destination": "tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn",
+                "amount": 0.0015,
+            })
+        );
+    }
+
+    #[test]
+    fn rpc_body_pay_to_max() {
+        let addr = BtcAddress::new("tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn");
+        let body = PayToOptions::new()
+            .to_builder(&addr, PayAmount::Max)
+            .build();
+
+        let actual = serde_json::to_value(&body).unwrap();
+        assert_eq!(
+            actual["params"],
+            json!({
+                "destination": "tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn",
+                "amount": "!",
+            })
+        );
+    }
+
+    #[test]
+    fn rpc_body_pay_to_from_coins() {
+        let addr = BtcAddress::new("tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn");
+        let coins = vec![
+            format!("{}:0", "a".repeat(64)),
+            format!("{}:1", "b".repeat(64)),
+        ];
+        let body = PayToOptions::new()
+            .fee_rate(Decimal::new(2, 0))
+            .from_coins(coins)
+            .to_builder(&addr, Amount::from_btc_checked(Decimal::new(1, 3)).unwrap())
+            .build();
+
+        let actual = serde_json::to_value(&body).unwrap();
+        assert_eq!(actual["method"], "payto");
+        assert_eq!(
+            actual["params"],
+            json!({
+                "destination": "tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn",
+                "amount": 0.001,
+                "feerate": "2",
+                "from_coins": format!("{}:0,{}:1", "a".repeat(64), "b".repeat(64)),
+            })
+        );
+    }
+
+    #[test]
+    fn rpc_body_get_transaction_options() {
+        let txid = "f".repeat(64);
+
+        let body = GetTransactionOptions::default().to_body(&txid);
+        let actual = serde_json::to_value(&body).unwrap();
+        assert_eq!(actual["method"], "gettransaction");
+        assert_eq!(actual["params"], json!({ "txid": txid }));
+
+        let options = GetTransactionOptions {
+            verbose: true,
+            wallet: Some(PathBuf::from("/wallets/savings")),
+        };
+        let actual = serde_json::to_value(options.to_body(&txid)).unwrap();
+        assert_eq!(
+            actual["params"],
+            json!({"txid": txid, "wallet_path": "/wallets/savings"})
+        );
+    }
+
+    #[tokio::test]
+    async fn get_transaction_verbose() {
+        let daemon = MockDaemon::start(|_, req| match req["method"].as_str() {
+            Some("gettransaction") => MockReply::result(json!("0200")),
+            _ => MockReply::result(json!({"version": 2, "inputs": [], "outputs": []})),
+        });
+        let electrum = daemon.electrum();
+        let options = GetTransactionOptions {
+            verbose: true,
+            wallet: None,
+        };
+
+        let tx = electrum
+            .get_transaction_with_options(&"f".repeat(64), &options)
+            .await
+            .unwrap();
+
+        assert_eq!(tx["version"], 2);
+        assert_eq!(daemon.requests()[1].body["params"]["tx"], "0200");
+    }
+
+    #[tokio::test]
+    async fn deserialize_transaction() {
+        use crate::ext::fixtures;
+
+        let daemon = MockDaemon::start(|_, _| MockReply::raw(fixtures::DESERIALIZE));
+
+        let tx = daemon
+            .electrum()
+            .deserialize_transaction("0200")
+            .await
+            .unwrap();
+        assert_eq!(tx.version, 2);
+        assert_eq!(tx.locktime, 1970116);
+        assert_eq!(tx.inputs.len(), 1);
+        assert_eq!(tx.inputs[0].prevout_n, 0);
+        assert_eq!(tx.inputs[0].nsequence, 0xffff_fffd);
+        assert!(!tx.inputs[0].coinbase);
+
+        let outputs: Vec<_> = tx
+            .outputs
+            .iter()
+            .map(|output| {
+                (
+                    output.address.as_ref().unwrap().address.as_str(),
+                    output.value_sats,
+                )
+            })
+            .collect();
+        assert_eq!(
+            outputs,
+            [
+                ("tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn", 1000),
+                ("tb1q6fezqhhcr8trzy33cpspftk4e7csqqy7zckyz0", 8800),
+            ]
+        );
+
+        let requests = daemon.requests();
+        assert_eq!(requests[0].body["method"], "deserialize");
+        assert_eq!(requests[0].body["params"], json!({"tx": "0200"}));
+    }
+
+    #[test]
+    fn rpc_body_sign_message_derivation_path() {
         let electrum = get_electrum_rpc();
-        let port = electrum.address.port();
-        assert_eq!(port.unwrap().as_u16(), 7000);
+        let addr = BtcAddress::new("tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn");
 
-        let host = electrum.address.host();
-        assert_eq!(host, Some("127.0.0.1"));
+        let body = electrum
+            .sign_message_body(&addr, "hello", Some("m/84'/1'/0'/0/3"))
+            .build();
 
-        let encoded_creds = electrum.auth.split(' ').collect::<Vec<&str>>()[1];
-        let decoded_creds = base64::decode(encoded_creds).unwrap();
-        assert_eq!("test:test", std::str::from_utf8(&decoded_creds).unwrap());
+        let actual = serde_json::to_value(&body).unwrap();
+        assert_eq!(actual["method"], "signmessage");
+        assert_eq!(
+            actual["params"],
+            json!({
+                "address": "tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn",
+                "message": "hello",
+                "derivation_path": "m/84'/1'/0'/0/3",
+            })
+        );
     }
 
     #[test]
-    #[should_panic]
-    fn new_electrum_instance_empty_address() {
-        Electrum::new(LOGIN.clone(), PASSWORD.clone(), "".to_string()).unwrap();
+    fn rpc_body_restore_from_descriptor() {
+        let descriptor = "wpkh([d34db33f/84h/1h/0h]tpubD6NzVbkrYhZ4WaWSyoBvQwbpLkojyoTZPRsgXELWz3Popb3qkjcJyJUGLnL4qHHoQvao8ESaAstxYSnhyswJ76uZPStJRJCTKvosUCJZL5B/0/*)";
+
+        let body = restore_from_descriptor_body(descriptor).unwrap();
+
+        let actual = serde_json::to_value(&body).unwrap();
+        assert_eq!(actual["method"], "restore");
+        assert_eq!(actual["params"], json!({ "text": descriptor }));
+    }
+
+    #[tokio::test]
+    async fn restore_from_malformed_descriptor() {
+        let daemon = MockDaemon::start(|_, _| MockReply::result(json!({})));
+        let electrum = daemon.electrum();
+
+        let res = electrum
+            .restore_from_descriptor("wpkh(tpubD6NzVbkrYhZ4")
+            .await;
+
+        assert!(matches!(res, Err(ElectrumRpcError::InvalidParams(_))));
+        assert_eq!(daemon.calls(), 0);
     }
 
     #[test]
-    fn error_casting_address_error() {
-        let electrum = Electrum::new(LOGIN.clone(), PASSWORD.clone(), "".to_string());
+    fn rpc_body_set_config() {
+        let body = set_config_body("use_rbf", Value::from(true));
 
-        assert!(matches!(
-            electrum,
-            Err(ElectrumRpcError::AddressError(InvalidUri { .. }))
-        ))
+        let actual = serde_json::to_value(&body).unwrap();
+        assert_eq!(actual["method"], "setconfig");
+        assert_eq!(actual["params"], json!({"key": "use_rbf", "value": true}));
+    }
+
+    #[tokio::test]
+    async fn get_mpk() {
+        let xpub = "vpub5VfkVzoT7qgd5gUKjxgGE2oMJU4zKSktusfLx2NaQCTfSeeSY3S723qXKUZZaJzaF6YaF8nwQgbMTWx54Ugkf4NZvSxdzicENHoLJh96EKg";
+        let daemon = MockDaemon::start(move |_, _| MockReply::result(json!(xpub)));
+
+        assert_eq!(daemon.electrum().get_mpk().await.unwrap(), xpub);
+        assert_eq!(daemon.requests()[0].body["method"], "getmpk");
+    }
+
+    #[test]
+    fn rpc_body_redacted() {
+        let body = JsonRpcBody::new()
+            .method(ElectrumMethod::SignTransaction)
+            .add_param(Param::Password, json!("secret"))
+            .build();
+
+        let redacted = body.redacted().unwrap();
+        assert!(!redacted.contains("secret"));
+        assert!(redacted.contains(r#""password":"***""#));
+
+        let body = JsonRpcBody::new()
+            .method(ElectrumMethod::RestoreWallet)
+            .add_param(Param::Text, json!("wild father tree"))
+            .add_param(Param::Passphrase, json!("extra words"))
+            .build();
+
+        let redacted = body.redacted().unwrap();
+        assert!(!redacted.contains("wild father"));
+        assert!(!redacted.contains("extra words"));
     }
 
     #[test]
@@ -593,7 +5224,187 @@ mod tests {
             .build();
 
         let actual = serde_json::to_string(&body).unwrap();
-        let expected = r#"{"json_rpc":2.0,"id":1111,"method":"getinfo","params":{}}"#;
+        let expected = r#"{"jsonrpc":"2.0","id":1111,"method":"getinfo","params":{}}"#;
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn rpc_body_envelope() {
+        let body = JsonRpcBody::new()
+            .method(ElectrumMethod::GetAddressBalance)
+            .add_param(
+                Param::BtcAddress,
+                json!("tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn"),
+            )
+            .build();
+
+        let payload = body.to_payload(EmptyParams::Object).unwrap();
+        let expected = r#"{"jsonrpc":"2.0","id":0,"method":"getaddressbalance","params":{"address":"tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn"}}"#;
+        assert_eq!(expected, payload);
+
+        let envelope: Value = serde_json::from_str(&payload).unwrap();
+        assert_eq!(envelope["jsonrpc"], "2.0");
+        assert!(envelope.get("json_rpc").is_none());
+    }
+
+    #[test]
+    fn debug_request_bytes_matches_wire() {
+        let electrum = get_electrum_rpc();
+        let params = [("tx", json!("0200")), ("password", json!("secret"))];
+
+        let bytes = electrum
+            .debug_request_bytes(electrum.next_id(), "signtransaction", &params, false)
+            .unwrap();
+        let expected = br#"{"jsonrpc":"2.0","id":0,"method":"signtransaction","params":{"tx":"0200","password":"secret"}}"#;
+        assert_eq!(bytes, expected.to_vec());
+
+        let body = JsonRpcBody::new()
+            .method(ElectrumMethod::SignTransaction)
+            .add_param(Param::Transaction, json!("0200"))
+            .add_param(Param::Password, json!("secret"))
+            .build();
+        let payload = body.to_payload(EmptyParams::Object).unwrap();
+        assert_eq!(bytes, payload.into_bytes());
+
+        let bytes = electrum
+            .debug_request_bytes(7, "signtransaction", &params, true)
+            .unwrap();
+        let expected = br#"{"jsonrpc":"2.0","id":7,"method":"signtransaction","params":{"tx":"0200","password":"***"}}"#;
+        assert_eq!(bytes, expected.to_vec());
+
+        let bytes = electrum.debug_request_bytes(0, "help", &[], true).unwrap();
+        assert_eq!(
+            bytes,
+            br#"{"jsonrpc":"2.0","id":0,"method":"help","params":{}}"#.to_vec()
+        );
+    }
+
+    #[test]
+    fn outputs_from_tuples() {
+        let addr = BtcAddress::new("tb1q6fezqhhcr8trzy33cpspftk4e7csqqy7zckyz0");
+        let outputs: Vec<Output> = vec![
+            (
+                "tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn",
+                Decimal::new(1, 1),
+            )
+                .into(),
+            ("tb1qaaa".to_string(), Decimal::new(25, 4)).into(),
+            (&addr, Decimal::new(1, 8)).into(),
+        ];
+
+        let actual = serde_json::to_string(&outputs).unwrap();
+        let expected = r#"[["tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn","0.1"],["tb1qaaa","0.0025"],["tb1q6fezqhhcr8trzy33cpspftk4e7csqqy7zckyz0","0.00000001"]]"#;
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn rpc_body_params_ordered() {
+        let build = || {
+            JsonRpcBody::new()
+                .method(ElectrumMethod::PayTo)
+                .add_param(
+                    Param::Destination,
+                    json!("tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn"),
+                )
+                .add_param(Param::Amount, json!("0.001"))
+                .add_param(Param::FeeRate, json!("2"))
+                .add_param(Param::Memo, json!("rent"))
+                .add_param(Param::Amount, json!("0.002"))
+                .build()
+        };
+
+        let expected = r#"{"jsonrpc":"2.0","id":0,"method":"payto","params":{"destination":"tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn","amount":"0.002","feerate":"2","memo":"rent"}}"#;
+        for _ in 0..10 {
+            let actual = build().to_payload(EmptyParams::Object).unwrap();
+            assert_eq!(expected, actual);
+        }
+    }
+
+    #[test]
+    fn rpc_body_empty_params() {
+        let body = JsonRpcBody::new().method(ElectrumMethod::Help).build();
+
+        let actual = body.to_payload(EmptyParams::Object).unwrap();
+        let expected = r#"{"jsonrpc":"2.0","id":0,"method":"help","params":{}}"#;
+        assert_eq!(expected, actual);
+
+        let actual = body.to_payload(EmptyParams::Array).unwrap();
+        let expected = r#"{"jsonrpc":"2.0","id":0,"method":"help","params":[]}"#;
+        assert_eq!(expected, actual);
+
+        let body = JsonRpcBody::new()
+            .method(ElectrumMethod::Broadcast)
+            .add_param(Param::Transaction, json!("00"))
+            .build();
+
+        let actual = body.to_payload(EmptyParams::Array).unwrap();
+        let expected = r#"{"jsonrpc":"2.0","id":0,"method":"broadcast","params":{"tx":"00"}}"#;
+        assert_eq!(expected, actual);
+    }
+
+    #[tokio::test]
+    async fn empty_params_array_sent() {
+        let daemon = MockDaemon::start(|_, _| MockReply::result(json!({})));
+        let electrum = Electrum::builder(LOGIN.clone(), PASSWORD.clone(), daemon.address())
+            .with_empty_params(EmptyParams::Array)
+            .build()
+            .unwrap();
+
+        electrum.get_info_raw().await.unwrap();
+        assert_eq!(daemon.requests()[0].body["params"], json!([]));
+    }
+
+    #[tokio::test]
+    async fn empty_params_per_method() {
+        let daemon = MockDaemon::start(|_, _| MockReply::result(json!({})));
+        let electrum = Electrum::builder(LOGIN.clone(), PASSWORD.clone(), daemon.address())
+            .with_empty_params_for("getinfo", EmptyParams::Array)
+            .build()
+            .unwrap();
+
+        electrum.get_info_raw().await.unwrap();
+        electrum.get_help().await.unwrap();
+        electrum.call_raw("getinfo", Value::Null).await.unwrap();
+
+        let requests = daemon.requests();
+        assert_eq!(requests[0].body["params"], json!([]));
+        assert_eq!(requests[1].body["params"], json!({}));
+        assert_eq!(requests[2].body["params"], json!([]));
+
+        let electrum = Electrum::builder(LOGIN.clone(), PASSWORD.clone(), daemon.address())
+            .with_empty_params(EmptyParams::Array)
+            .with_empty_params_for("help", EmptyParams::Object)
+            .build()
+            .unwrap();
+        let bytes = electrum.debug_request_bytes(0, "help", &[], false).unwrap();
+        assert!(str::from_utf8(&bytes).unwrap().ends_with(r#""params":{}}"#));
+        let bytes = electrum
+            .debug_request_bytes(0, "version", &[], false)
+            .unwrap();
+        assert!(str::from_utf8(&bytes).unwrap().ends_with(r#""params":[]}"#));
+    }
+
+    #[test]
+    fn rpc_body_create_new_address() {
+        let body = JsonRpcBody::new()
+            .method(ElectrumMethod::CreateNewAddress)
+            .build();
+
+        let actual = serde_json::to_string(&body).unwrap();
+        let expected = r#"{"jsonrpc":"2.0","id":0,"method":"createnewaddress","params":{}}"#;
         assert_eq!(expected, actual);
     }
+
+    #[tokio::test]
+    async fn parse_result_owned_address() {
+        let resp = Response::new(Body::from(
+            r#"{"jsonrpc":"2.0","result":"tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn","id":0}"#,
+        ));
+
+        let address: OwnedBtcAddress = parse_result(resp, &json!(0), false).await.unwrap();
+        assert_eq!(
+            address.as_btc_address().address,
+            "tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn"
+        );
+    }
 }
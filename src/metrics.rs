@@ -0,0 +1,168 @@
+//! Client side metrics of json-rpc calls.
+
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Upper bounds of the latency histogram buckets in seconds.
+const LATENCY_BUCKETS: [f64; 10] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0];
+
+/// Counters of the calls made by `Electrum` client.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    calls: AtomicU64,
+    errors: AtomicU64,
+    latency_sum_micros: AtomicU64,
+    latency_buckets: [AtomicU64; LATENCY_BUCKETS.len()],
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+}
+
+impl Metrics {
+    pub(crate) fn record(&self, latency: Duration, success: bool) {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        if !success {
+            self.errors.fetch_add(1, Ordering::Relaxed);
+        }
+
+        self.latency_sum_micros
+            .fetch_add(latency.as_micros() as u64, Ordering::Relaxed);
+
+        let secs = latency.as_secs_f64();
+        for (bound, bucket) in LATENCY_BUCKETS.iter().zip(&self.latency_buckets) {
+            if secs <= *bound {
+                bucket.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    pub(crate) fn record_sent(&self, bytes: usize) {
+        self.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_received(&self, bytes: usize) {
+        self.bytes_received
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Number of calls made.
+    pub fn calls(&self) -> u64 {
+        self.calls.load(Ordering::Relaxed)
+    }
+
+    /// Number of calls failed on the transport level.
+    pub fn errors(&self) -> u64 {
+        self.errors.load(Ordering::Relaxed)
+    }
+
+    /// Total time spent in calls.
+    pub fn latency_sum(&self) -> Duration {
+        Duration::from_micros(self.latency_sum_micros.load(Ordering::Relaxed))
+    }
+
+    /// Total size of request bodies sent.
+    pub fn bytes_sent(&self) -> u64 {
+        self.bytes_sent.load(Ordering::Relaxed)
+    }
+
+    /// Total size of response bodies received.
+    pub fn bytes_received(&self) -> u64 {
+        self.bytes_received.load(Ordering::Relaxed)
+    }
+
+    /// Render the metrics in Prometheus text exposition format.
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+
+        writeln!(
+            out,
+            "# HELP electrum_rpc_calls_total Number of json-rpc calls made."
+        )
+        .unwrap();
+        writeln!(out, "# TYPE electrum_rpc_calls_total counter").unwrap();
+        writeln!(out, "electrum_rpc_calls_total {}", self.calls()).unwrap();
+
+        writeln!(
+            out,
+            "# HELP electrum_rpc_errors_total Number of json-rpc calls failed."
+        )
+        .unwrap();
+        writeln!(out, "# TYPE electrum_rpc_errors_total counter").unwrap();
+        writeln!(out, "electrum_rpc_errors_total {}", self.errors()).unwrap();
+
+        writeln!(
+            out,
+            "# HELP electrum_rpc_latency_seconds Latency of json-rpc calls."
+        )
+        .unwrap();
+        writeln!(out, "# TYPE electrum_rpc_latency_seconds histogram").unwrap();
+        for (bound, bucket) in LATENCY_BUCKETS.iter().zip(&self.latency_buckets) {
+            writeln!(
+                out,
+                "electrum_rpc_latency_seconds_bucket{{le=\"{}\"}} {}",
+                bound,
+                bucket.load(Ordering::Relaxed)
+            )
+            .unwrap();
+        }
+        writeln!(
+            out,
+            "electrum_rpc_latency_seconds_bucket{{le=\"+Inf\"}} {}",
+            self.calls()
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "electrum_rpc_latency_seconds_sum {}",
+            self.latency_sum().as_secs_f64()
+        )
+        .unwrap();
+        writeln!(out, "electrum_rpc_latency_seconds_count {}", self.calls()).unwrap();
+
+        writeln!(
+            out,
+            "# HELP electrum_rpc_sent_bytes_total Size of request bodies sent."
+        )
+        .unwrap();
+        writeln!(out, "# TYPE electrum_rpc_sent_bytes_total counter").unwrap();
+        writeln!(out, "electrum_rpc_sent_bytes_total {}", self.bytes_sent()).unwrap();
+
+        writeln!(
+            out,
+            "# HELP electrum_rpc_received_bytes_total Size of response bodies received."
+        )
+        .unwrap();
+        writeln!(out, "# TYPE electrum_rpc_received_bytes_total counter").unwrap();
+        writeln!(
+            out,
+            "electrum_rpc_received_bytes_total {}",
+            self.bytes_received()
+        )
+        .unwrap();
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prometheus_output() {
+        let metrics = Metrics::default();
+        metrics.record(Duration::from_millis(20), true);
+        metrics.record(Duration::from_secs(2), false);
+        metrics.record_sent(120);
+        metrics.record_received(64);
+
+        let out = metrics.to_prometheus();
+        assert!(out.contains("electrum_rpc_calls_total 2\n"));
+        assert!(out.contains("electrum_rpc_errors_total 1\n"));
+        assert!(out.contains("electrum_rpc_latency_seconds_bucket{le=\"0.025\"} 1\n"));
+        assert!(out.contains("electrum_rpc_latency_seconds_bucket{le=\"+Inf\"} 2\n"));
+        assert!(out.contains("electrum_rpc_latency_seconds_count 2\n"));
+        assert!(out.contains("electrum_rpc_sent_bytes_total 120\n"));
+        assert!(out.contains("electrum_rpc_received_bytes_total 64\n"));
+    }
+}
@@ -0,0 +1,110 @@
+//! Polling the daemon until a condition is met.
+
+use std::convert::TryFrom;
+use std::future::Future;
+use std::time::Duration;
+
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+use crate::error::{ElectrumRpcError, Result};
+
+/// How often and how long the `wait_*` helpers poll the daemon.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PollConfig {
+    /// Delay before the second check.
+    pub interval: Duration,
+
+    /// Give up with `ElectrumRpcError::Timeout` after this long.
+    pub timeout: Duration,
+
+    /// Give up with `ElectrumRpcError::Timeout` after this many checks,
+    /// even if `timeout` is not reached yet.
+    pub max_attempts: Option<u32>,
+
+    /// Factor the delay grows by after each check, `1.0` keeps it constant.
+    pub backoff: f64,
+}
+
+impl PollConfig {
+    /// Poll every `interval` for at most `timeout`, without backoff.
+    pub fn new(interval: Duration, timeout: Duration) -> Self {
+        Self {
+            interval,
+            timeout,
+            ..Self::default()
+        }
+    }
+
+    /// Give up after `max_attempts` checks.
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = Some(max_attempts);
+        self
+    }
+
+    /// Multiply the delay by `backoff` after each check.
+    pub fn with_backoff(mut self, backoff: f64) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Delay after check number `attempt` (zero based).
+    pub(crate) fn delay(&self, attempt: u32) -> Duration {
+        let factor = self
+            .backoff
+            .powi(i32::try_from(attempt).unwrap_or(i32::MAX));
+        Duration::try_from_secs_f64(self.interval.as_secs_f64() * factor).unwrap_or(Duration::MAX)
+    }
+}
+
+impl Default for PollConfig {
+    /// Every 10 seconds for at most 10 minutes.
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(10),
+            timeout: Duration::from_secs(10 * 60),
+            max_attempts: None,
+            backoff: 1.0,
+        }
+    }
+}
+
+/// Call `check` as configured by `config` until it returns `Some`, failing with
+/// `ElectrumRpcError::Timeout` after `config.timeout` or `config.max_attempts`
+/// checks, or `ElectrumRpcError::Cancelled` once `cancel` is triggered.
+pub(crate) async fn poll_until<T, F, Fut>(
+    config: &PollConfig,
+    cancel: Option<&CancellationToken>,
+    mut check: F,
+) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<Option<T>>>,
+{
+    let deadline = Instant::now() + config.timeout;
+    let never = CancellationToken::new();
+    let cancel = cancel.unwrap_or(&never);
+    let mut attempt = 0;
+
+    loop {
+        let checked = tokio::select! {
+            _ = cancel.cancelled() => return Err(ElectrumRpcError::Cancelled),
+            checked = check() => checked?,
+        };
+
+        if let Some(value) = checked {
+            return Ok(value);
+        }
+
+        let now = Instant::now();
+        if now >= deadline || config.max_attempts.is_some_and(|max| attempt + 1 >= max) {
+            return Err(ElectrumRpcError::Timeout);
+        }
+
+        tokio::select! {
+            _ = cancel.cancelled() => return Err(ElectrumRpcError::Cancelled),
+            _ = tokio::time::sleep(config.delay(attempt).min(deadline - now)) => {}
+        }
+        attempt += 1;
+    }
+}
@@ -0,0 +1,731 @@
+//! Typed results of Electrum's json-rpc calls.
+
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::path::PathBuf;
+
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Deserializer};
+
+use crate::btc::OwnedBtcAddress;
+
+/// Wallet or address balance in BTC.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Balance {
+    pub confirmed: Decimal,
+
+    #[serde(default)]
+    pub unconfirmed: Decimal,
+}
+
+impl Balance {
+    /// Change from `other` (the previous snapshot) to `self`.
+    pub fn diff(&self, other: &Balance) -> BalanceDelta {
+        BalanceDelta {
+            confirmed: self.confirmed - other.confirmed,
+            unconfirmed: self.unconfirmed - other.unconfirmed,
+        }
+    }
+}
+
+/// Signed change between two balance snapshots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BalanceDelta {
+    pub confirmed: Decimal,
+    pub unconfirmed: Decimal,
+}
+
+impl BalanceDelta {
+    pub fn is_zero(&self) -> bool {
+        self.confirmed.is_zero() && self.unconfirmed.is_zero()
+    }
+}
+
+/// Network and sync state of the daemon, as reported by `getinfo`.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct GetInfoResponse {
+    /// Height of the local chain of the daemon.
+    pub blockchain_height: u64,
+
+    /// Height reported by the Electrum server.
+    pub server_height: u64,
+    pub connected: bool,
+
+    /// Electrum version of the daemon.
+    pub version: String,
+
+    /// Data directory of the daemon.
+    pub path: PathBuf,
+
+    /// Whether the daemon picks its server itself rather than sticking
+    /// to `server`. Not reported by every version.
+    #[serde(default)]
+    pub auto_connect: Option<bool>,
+
+    /// Host of the Electrum server the daemon is connected to.
+    #[serde(default)]
+    pub server: Option<String>,
+
+    /// Electrum protocol version spoken with `server`.
+    #[serde(default)]
+    pub protocol_version: Option<String>,
+
+    /// Whether Lightning is enabled, reported since Electrum 4.1.
+    #[serde(default)]
+    pub lightning: Option<bool>,
+
+    /// Fee rate estimates of the server, reported by some versions.
+    #[serde(default)]
+    pub fee_estimates: FeeEstimates,
+}
+
+/// Merkle branch of a transaction in a block, for SPV verification.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MerkleProof {
+    pub block_height: u64,
+    pub merkle: Vec<String>,
+    pub pos: u64,
+}
+
+/// Confirmation status of a wallet transaction.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TxStatus {
+    pub confirmations: u64,
+}
+
+/// Unspent output of the wallet.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Utxo {
+    pub address: OwnedBtcAddress,
+
+    /// Value in BTC.
+    pub value: Decimal,
+    pub prevout_hash: String,
+    pub prevout_n: u32,
+
+    /// Block height, zero or negative for unconfirmed outputs.
+    pub height: i64,
+
+    #[serde(default)]
+    pub coinbase: bool,
+}
+
+impl Utxo {
+    pub fn is_confirmed(&self) -> bool {
+        self.height > 0
+    }
+}
+
+/// Sum of the values of `utxos` in BTC.
+pub fn total_value(utxos: &[Utxo]) -> Decimal {
+    utxos.iter().map(|utxo| utxo.value).sum()
+}
+
+/// Wallet address from `listaddresses`. Balance and label are only
+/// reported when asked for, see `ListAddressesOptions`.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(try_from = "ListedAddress")]
+pub struct AddressEntry {
+    pub address: OwnedBtcAddress,
+    pub label: Option<String>,
+
+    /// Balance in BTC.
+    pub balance: Option<Decimal>,
+}
+
+/// `listaddresses` item: a bare address, or with `balance` or `labels`
+/// a tuple of the address, its balance and the Python repr of its label,
+/// e.g. `["tb1q...", "0.001", "'savings'"]`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ListedAddress {
+    Bare(OwnedBtcAddress),
+    Tuple(Vec<String>),
+}
+
+impl TryFrom<ListedAddress> for AddressEntry {
+    type Error = String;
+
+    fn try_from(listed: ListedAddress) -> Result<Self, Self::Error> {
+        let fields = match listed {
+            ListedAddress::Bare(address) => {
+                return Ok(Self {
+                    address,
+                    label: None,
+                    balance: None,
+                })
+            }
+            ListedAddress::Tuple(fields) => fields,
+        };
+
+        let mut fields = fields.into_iter();
+        let address = fields.next().ok_or("empty listaddresses tuple")?;
+        let mut entry = Self {
+            address: OwnedBtcAddress::new(address),
+            label: None,
+            balance: None,
+        };
+
+        // A label is always quoted, a balance never is.
+        for field in fields {
+            if field.starts_with('\'') || field.starts_with('"') {
+                let label = unrepr(&field).ok_or_else(|| format!("invalid label {}", field))?;
+                entry.label = Some(label).filter(|label| !label.is_empty());
+            } else {
+                let balance = field
+                    .trim()
+                    .parse()
+                    .map_err(|e| format!("invalid balance {:?}: {}", field, e))?;
+                entry.balance = Some(balance);
+            }
+        }
+
+        Ok(entry)
+    }
+}
+
+/// String of the Python repr `repr` of a str, `None` if it isn't one.
+fn unrepr(repr: &str) -> Option<String> {
+    let quote = repr.chars().next().filter(|&c| c == '\'' || c == '"')?;
+    let inner = repr.strip_prefix(quote)?.strip_suffix(quote)?;
+
+    let mut unquoted = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unquoted.push(c);
+            continue;
+        }
+
+        let escaped = match chars.next()? {
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            c @ ('x' | 'u' | 'U') => {
+                let digits = match c {
+                    'x' => 2,
+                    'u' => 4,
+                    _ => 8,
+                };
+                let hex: String = chars.by_ref().take(digits).collect();
+                u32::from_str_radix(&hex, 16)
+                    .ok()
+                    .and_then(char::from_u32)?
+            }
+            c => c,
+        };
+        unquoted.push(escaped);
+    }
+
+    Some(unquoted)
+}
+
+/// Transaction touching an address.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct HistoryEntry {
+    pub tx_hash: String,
+
+    /// Block height, zero or negative for unconfirmed transactions.
+    pub height: i64,
+
+    /// Fee in satoshis, reported for unconfirmed transactions.
+    #[serde(default)]
+    pub fee: Option<u64>,
+}
+
+impl HistoryEntry {
+    pub fn is_confirmed(&self) -> bool {
+        self.height > 0
+    }
+}
+
+/// Wallet transaction as listed by `onchain_history`, e.g. for accounting exports.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TxRecord {
+    pub txid: String,
+
+    /// Block time in unix seconds, `None` for unconfirmed transactions.
+    pub timestamp: Option<u64>,
+
+    /// Change of the wallet balance in BTC, negative for outgoing transactions.
+    #[serde(rename = "bc_value", alias = "value")]
+    pub amount: Decimal,
+
+    /// Wallet balance in BTC after the transaction.
+    #[serde(rename = "bc_balance", alias = "balance")]
+    pub balance: Decimal,
+
+    /// Fee in satoshis, `None` if the wallet doesn't know all input values.
+    #[serde(default)]
+    pub fee_sat: Option<u64>,
+
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub label: Option<String>,
+}
+
+fn empty_as_none<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    let label = Option::<String>::deserialize(deserializer)?;
+    Ok(label.filter(|label| !label.is_empty()))
+}
+
+/// CSV export of transaction records.
+pub trait ToCsv {
+    /// Records as CSV with a `txid,timestamp,amount,balance,label` header row.
+    /// Missing values are left empty.
+    fn to_csv(&self) -> String;
+}
+
+impl ToCsv for [TxRecord] {
+    fn to_csv(&self) -> String {
+        let mut csv = String::from("txid,timestamp,amount,balance,label\n");
+        for record in self {
+            let timestamp = record.timestamp.map(|t| t.to_string()).unwrap_or_default();
+            let label = record.label.as_deref().map(csv_field).unwrap_or_default();
+            csv.push_str(&format!(
+                "{},{},{},{},{}\n",
+                record.txid, timestamp, record.amount, record.balance, label
+            ));
+        }
+
+        csv
+    }
+}
+
+/// Quote `field` if it contains a separator, quote or line break.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Sort by block height ascending, keeping unconfirmed entries last
+/// in their original order.
+pub fn sort_history_by_height(history: &mut [HistoryEntry]) {
+    history.sort_by_key(|entry| {
+        if entry.is_confirmed() {
+            (false, entry.height)
+        } else {
+            (true, 0)
+        }
+    });
+}
+
+/// Type and derivation details of the loaded wallet.
+/// Fields the daemon doesn't report are left at their defaults.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct WalletDetails {
+    /// e.g. `standard`, `2of3`, `imported`.
+    pub wallet_type: String,
+    pub gap_limit: Option<u32>,
+    #[serde(alias = "keystore")]
+    pub keystores: Vec<KeystoreInfo>,
+    pub seed_available: bool,
+}
+
+/// Keystore of a wallet, one per cosigner.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct KeystoreInfo {
+    /// e.g. `bip32`, `hardware`, `imported`.
+    #[serde(rename = "type")]
+    pub keystore_type: String,
+    pub derivation: Option<String>,
+    pub root_fingerprint: Option<String>,
+}
+
+/// Result of signing a PSBT with the wallet keys.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(from = "SignResult")]
+pub struct SignedPsbt {
+    /// PSBT with the signatures added, or the final transaction hex
+    /// once all signatures are present.
+    pub psbt: String,
+
+    /// Whether all signatures are present and the transaction can be broadcast.
+    pub complete: bool,
+}
+
+/// `signtransaction` result: a bare string since Electrum 4, an object before.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SignResult {
+    Serialized(String),
+    Object { hex: String, complete: bool },
+}
+
+/// Magic prefix of PSBTs encoded in base64 and hex.
+const PSBT_MAGIC: [&str; 2] = ["cHNidP8", "70736274ff"];
+
+impl From<SignResult> for SignedPsbt {
+    fn from(result: SignResult) -> Self {
+        match result {
+            SignResult::Serialized(psbt) => Self {
+                complete: !PSBT_MAGIC.iter().any(|magic| psbt.starts_with(magic)),
+                psbt,
+            },
+            SignResult::Object { hex, complete } => Self {
+                psbt: hex,
+                complete,
+            },
+        }
+    }
+}
+
+/// Transaction created by `payto`.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(from = "PayToResult")]
+pub struct CreatedTransaction {
+    /// Signed transaction hex, or a PSBT if it is not fully signed.
+    pub hex: String,
+
+    /// Whether the transaction is fully signed and can be broadcast.
+    pub complete: bool,
+
+    /// Txid of the transaction, when reported by the daemon.
+    pub final_txid: Option<String>,
+}
+
+/// `payto` result: a bare string since Electrum 4, an object before.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PayToResult {
+    Serialized(String),
+    Object {
+        hex: String,
+        complete: bool,
+        #[serde(default, alias = "final_txid")]
+        txid: Option<String>,
+    },
+}
+
+impl From<PayToResult> for CreatedTransaction {
+    fn from(result: PayToResult) -> Self {
+        match result {
+            PayToResult::Serialized(hex) => Self {
+                complete: !PSBT_MAGIC.iter().any(|magic| hex.starts_with(magic)),
+                hex,
+                final_txid: None,
+            },
+            PayToResult::Object {
+                hex,
+                complete,
+                txid,
+            } => Self {
+                hex,
+                complete,
+                final_txid: txid,
+            },
+        }
+    }
+}
+
+/// Raw transaction decoded by the daemon's `deserialize`.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DeserializedTx {
+    pub version: u32,
+    pub inputs: Vec<TxInput>,
+    pub outputs: Vec<TxOutput>,
+
+    #[serde(alias = "lockTime")]
+    pub locktime: u32,
+}
+
+/// Input of a [`DeserializedTx`].
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TxInput {
+    pub prevout_hash: String,
+    pub prevout_n: u32,
+
+    #[serde(alias = "sequence")]
+    pub nsequence: u32,
+
+    #[serde(default)]
+    pub coinbase: bool,
+}
+
+/// Output of a [`DeserializedTx`].
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TxOutput {
+    /// `None` for outputs without an address, e.g. OP_RETURN.
+    #[serde(default)]
+    pub address: Option<OwnedBtcAddress>,
+
+    #[serde(alias = "scriptPubKey")]
+    pub scriptpubkey: String,
+
+    /// Value in satoshis.
+    #[serde(alias = "value")]
+    pub value_sats: u64,
+}
+
+/// Lightning payment request of the wallet.
+#[cfg(feature = "lightning")]
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct LnInvoice {
+    #[serde(alias = "lightning_invoice", alias = "invoice")]
+    pub bolt11: String,
+
+    /// Amount in BTC, `None` for any amount.
+    #[serde(default, rename = "amount_BTC")]
+    pub amount: Option<Decimal>,
+
+    /// Electrum request status: 0 unpaid, 1 expired, 2 unknown, 3 paid,
+    /// 4 inflight, 5 failed, 6 routing.
+    pub status: u64,
+
+    #[serde(alias = "rhash")]
+    pub payment_hash: String,
+}
+
+#[cfg(feature = "lightning")]
+impl LnInvoice {
+    pub fn is_paid(&self) -> bool {
+        self.status == 3
+    }
+}
+
+/// Fee rate estimates in sat/kvByte keyed by confirmation target in blocks.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(transparent)]
+pub struct FeeEstimates(pub BTreeMap<u32, Decimal>);
+
+impl FeeEstimates {
+    /// Number of blocks a transaction paying `fee_rate` (sat/vByte) likely
+    /// waits for confirmation, interpolated between the known targets.
+    /// `None` if the fee rate is below the estimate of every target.
+    pub fn confirmation_blocks(&self, fee_rate: Decimal) -> Option<u32> {
+        let fee_per_kvb = fee_rate * Decimal::from(1000);
+        let mut higher: Option<(u32, Decimal)> = None;
+
+        for (&target, &estimate) in &self.0 {
+            if fee_per_kvb >= estimate {
+                let (prev_target, prev_estimate) = match higher {
+                    Some(higher) => higher,
+                    None => return Some(target),
+                };
+
+                let span = Decimal::from(target - prev_target);
+                let blocks = Decimal::from(prev_target)
+                    + span * (prev_estimate - fee_per_kvb) / (prev_estimate - estimate);
+                return blocks.ceil().to_u32();
+            }
+            higher = Some((target, estimate));
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn balance(confirmed: i64, unconfirmed: i64) -> Balance {
+        Balance {
+            confirmed: Decimal::new(confirmed, 8),
+            unconfirmed: Decimal::new(unconfirmed, 8),
+        }
+    }
+
+    #[test]
+    fn balance_diff_increase() {
+        let delta = balance(1500, 200).diff(&balance(1000, 0));
+
+        assert_eq!(delta.confirmed, Decimal::new(500, 8));
+        assert_eq!(delta.unconfirmed, Decimal::new(200, 8));
+        assert!(!delta.is_zero());
+    }
+
+    #[test]
+    fn balance_diff_decrease() {
+        let delta = balance(1000, 0).diff(&balance(1500, 200));
+
+        assert_eq!(delta.confirmed, Decimal::new(-500, 8));
+        assert_eq!(delta.unconfirmed, Decimal::new(-200, 8));
+    }
+
+    #[test]
+    fn balance_diff_no_change() {
+        let delta = balance(1000, 200).diff(&balance(1000, 200));
+
+        assert!(delta.is_zero());
+    }
+
+    #[test]
+    fn balance_from_json() {
+        let balance: Balance =
+            serde_json::from_str(r#"{"confirmed": "0.0015", "unconfirmed": "0.0002"}"#).unwrap();
+
+        assert_eq!(balance.confirmed, Decimal::new(15, 4));
+        assert_eq!(balance.unconfirmed, Decimal::new(2, 4));
+    }
+
+    #[test]
+    fn history_sorted_by_height() {
+        let mut history: Vec<HistoryEntry> = serde_json::from_str(
+            r#"[
+                {"tx_hash": "c", "height": 0, "fee": 141},
+                {"tx_hash": "b", "height": 2001},
+                {"tx_hash": "d", "height": -1, "fee": 200},
+                {"tx_hash": "a", "height": 1999}
+            ]"#,
+        )
+        .unwrap();
+
+        sort_history_by_height(&mut history);
+
+        let order: Vec<&str> = history.iter().map(|e| e.tx_hash.as_str()).collect();
+        assert_eq!(order, vec!["a", "b", "c", "d"]);
+        assert_eq!(history[2].fee, Some(141));
+    }
+
+    #[test]
+    fn confirmation_blocks_from_estimates() {
+        let estimates: FeeEstimates =
+            serde_json::from_str(r#"{"2": 20000, "5": 10000, "10": 5000, "25": 1000}"#).unwrap();
+
+        assert_eq!(estimates.confirmation_blocks(Decimal::new(30, 0)), Some(2));
+        assert_eq!(estimates.confirmation_blocks(Decimal::new(10, 0)), Some(5));
+        assert_eq!(estimates.confirmation_blocks(Decimal::new(15, 0)), Some(4));
+        assert_eq!(estimates.confirmation_blocks(Decimal::new(3, 0)), Some(18));
+        assert_eq!(estimates.confirmation_blocks(Decimal::new(5, 1)), None);
+    }
+
+    #[test]
+    fn tx_records_to_csv() {
+        #[derive(Deserialize)]
+        struct History {
+            transactions: Vec<TxRecord>,
+        }
+
+        let mut json: serde_json::Value =
+            serde_json::from_str(crate::ext::fixtures::ONCHAIN_HISTORY).unwrap();
+        let history: History = serde_json::from_value(json["result"].take()).unwrap();
+        let records = history.transactions;
+
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].amount, Decimal::new(1, 3));
+        assert_eq!(records[1].amount, Decimal::new(-5, 4));
+        assert_eq!(records[1].balance, Decimal::new(5, 4));
+        assert_eq!(records[1].label.as_deref(), Some("rent, march"));
+        assert_eq!(records[2].timestamp, None);
+        assert_eq!(records[2].label, None);
+
+        assert_eq!(
+            records.to_csv(),
+            "txid,timestamp,amount,balance,label\n\
+             eaf3e55e521431bcd18294b8a536a966f5fbdc9f7701cdbaa877cec9e95b8cb5,1613739400,0.001,0.001,\n\
+             3d10b4ad1ad56e4e1e8ad6bc6fe9bb8d4d0c84d7c7b13e2b8c0a4b9f2a6c1d0e,1613825800,-0.0005,0.0005,\"rent, march\"\n\
+             9a1c2b3d4e5f60718293a4b5c6d7e8f90a1b2c3d4e5f60718293a4b5c6d7e8f9,,0.0002,0.0007,\n"
+        );
+    }
+
+    #[test]
+    fn signed_psbt_complete_and_incomplete() {
+        use crate::ext::fixtures;
+
+        let parse = |fixture: &str| {
+            let mut json: serde_json::Value = serde_json::from_str(fixture).unwrap();
+            serde_json::from_value::<SignedPsbt>(json["result"].take()).unwrap()
+        };
+
+        let signed = parse(fixtures::SIGN_PSBT_COMPLETE);
+        assert!(signed.complete);
+        assert!(signed.psbt.starts_with("0200"));
+
+        let signed = parse(fixtures::SIGN_PSBT_INCOMPLETE);
+        assert!(!signed.complete);
+        assert!(signed.psbt.starts_with("cHNidP8"));
+
+        let signed: SignedPsbt =
+            serde_json::from_str(r#"{"hex": "70736274ff0100", "complete": false}"#).unwrap();
+        assert!(!signed.complete);
+    }
+
+    #[test]
+    fn created_transaction_both_shapes() {
+        let created: CreatedTransaction = serde_json::from_str(r#""0200000001""#).unwrap();
+        assert_eq!(created.hex, "0200000001");
+        assert!(created.complete);
+        assert_eq!(created.final_txid, None);
+
+        let created: CreatedTransaction = serde_json::from_str(
+            r#"{
+                "hex": "0200000001",
+                "complete": true,
+                "final": false,
+                "txid": "eaf3e55e521431bcd18294b8a536a966f5fbdc9f7701cdbaa877cec9e95b8cb5"
+            }"#,
+        )
+        .unwrap();
+        assert!(created.complete);
+        assert_eq!(
+            created.final_txid.as_deref(),
+            Some("eaf3e55e521431bcd18294b8a536a966f5fbdc9f7701cdbaa877cec9e95b8cb5")
+        );
+
+        let created: CreatedTransaction = serde_json::from_str(r#""cHNidP8BAHEC""#).unwrap();
+        assert!(!created.complete);
+    }
+
+    #[test]
+    fn address_entries_both_shapes() {
+        use crate::ext::fixtures;
+
+        let parse = |fixture: &str| {
+            let mut json: serde_json::Value = serde_json::from_str(fixture).unwrap();
+            serde_json::from_value::<Vec<AddressEntry>>(json["result"].take()).unwrap()
+        };
+
+        let bare = parse(fixtures::LIST_ADDRESSES);
+        assert_eq!(bare.len(), 2);
+        assert_eq!(
+            bare[0].address.address,
+            "tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn"
+        );
+        assert_eq!(bare[0].label, None);
+        assert_eq!(bare[0].balance, None);
+
+        let detailed = parse(fixtures::LIST_ADDRESSES_DETAILED);
+        assert_eq!(detailed[0].address, bare[0].address);
+        assert_eq!(detailed[0].label.as_deref(), Some("savings"));
+        assert_eq!(detailed[0].balance, Some(Decimal::new(1, 3)));
+        assert_eq!(detailed[1].label, None);
+        assert_eq!(detailed[1].balance, Some(Decimal::ZERO));
+
+        let labels_only: Vec<AddressEntry> = serde_json::from_value(serde_json::json!([[
+            "tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn",
+            "\"Bob's\\nsavings\""
+        ]]))
+        .unwrap();
+        assert_eq!(labels_only[0].label.as_deref(), Some("Bob's\nsavings"));
+        assert_eq!(labels_only[0].balance, None);
+    }
+
+    #[test]
+    fn unrepr_python_strings() {
+        assert_eq!(unrepr("'savings'").as_deref(), Some("savings"));
+        assert_eq!(unrepr("''").as_deref(), Some(""));
+        assert_eq!(unrepr(r#""it's""#).as_deref(), Some("it's"));
+        assert_eq!(unrepr(r"'a\'b\\c'").as_deref(), Some("a'b\\c"));
+        assert_eq!(unrepr(r"'caf\xe9 \u20ac'").as_deref(), Some("café €"));
+        assert_eq!(unrepr("savings"), None);
+        assert_eq!(unrepr("'savings"), None);
+    }
+
+    #[test]
+    fn utxos_total_value() {
+        let mut json: serde_json::Value =
+            serde_json::from_str(crate::ext::fixtures::LIST_UNSPENT).unwrap();
+        let utxos: Vec<Utxo> = serde_json::from_value(json["result"].take()).unwrap();
+
+        assert_eq!(total_value(&utxos), Decimal::new(1512, 6));
+        assert_eq!(total_value(&[]), Decimal::ZERO);
+        assert!(utxos[0].is_confirmed());
+        assert!(!utxos[1].is_confirmed());
+    }
+}
@@ -0,0 +1,149 @@
+//! Retrying calls rejected by a rate-limiting gateway or failed on connection level.
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{Duration, SystemTime};
+
+use hyper::header::RETRY_AFTER;
+use hyper::{Body, Response};
+
+/// How many times and how long apart a rate-limited (`429`) or failed to
+/// connect call is retried.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct RetryPolicy {
+    pub(crate) max: u32,
+    pub(crate) base_delay: Duration,
+
+    /// Upper bound of any delay, `Retry-After` included.
+    pub(crate) max_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Delay before retry number `attempt` (zero based): the `Retry-After`
+    /// of `resp` if it has a valid one, exponential backoff otherwise,
+    /// at most `max_delay`.
+    pub(crate) fn delay(&self, attempt: u32, resp: &Response<Body>) -> Duration {
+        retry_after(resp, SystemTime::now())
+            .unwrap_or_else(|| self.backoff(attempt))
+            .min(self.max_delay)
+    }
+
+    /// Delay before retry number `attempt` (zero based) after a connection
+    /// error: exponential backoff with jitter, so clients cut off together
+    /// don't reconnect all at once.
+    pub(crate) fn connection_delay(&self, attempt: u32) -> Duration {
+        jitter(self.backoff(attempt), random_fraction())
+    }
+
+    fn backoff(&self, attempt: u32) -> Duration {
+        self.base_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_delay)
+    }
+}
+
+/// Somewhere between half of `delay` and `delay`, as picked by `fraction` in `0..1`.
+fn jitter(delay: Duration, fraction: f64) -> Duration {
+    let half = delay / 2;
+    half + half.mul_f64(fraction)
+}
+
+/// Pseudo-random number in `0..1`, good enough to spread out retries.
+fn random_fraction() -> f64 {
+    let random = RandomState::new().build_hasher().finish();
+    (random >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Parse `Retry-After` given either as delay seconds or as HTTP date.
+fn retry_after(resp: &Response<Body>, now: SystemTime) -> Option<Duration> {
+    let value = resp.headers().get(RETRY_AFTER)?.to_str().ok()?.trim();
+
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(date.duration_since(now).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rate_limited(retry_after: &str) -> Response<Body> {
+        Response::builder()
+            .status(429)
+            .header(RETRY_AFTER, retry_after)
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[test]
+    fn retry_after_seconds() {
+        let delay = retry_after(&rate_limited("120"), SystemTime::now());
+        assert_eq!(delay, Some(Duration::from_secs(120)));
+    }
+
+    #[test]
+    fn retry_after_http_date() {
+        let now = httpdate::parse_http_date("Wed, 21 Oct 2015 07:28:00 GMT").unwrap();
+
+        let delay = retry_after(&rate_limited("Wed, 21 Oct 2015 07:28:30 GMT"), now);
+        assert_eq!(delay, Some(Duration::from_secs(30)));
+
+        let delay = retry_after(&rate_limited("Wed, 21 Oct 2015 07:27:00 GMT"), now);
+        assert_eq!(delay, Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn retry_after_capped() {
+        let policy = RetryPolicy {
+            max: 3,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(60),
+        };
+
+        assert_eq!(
+            policy.delay(0, &rate_limited("86400")),
+            Duration::from_secs(60)
+        );
+        assert_eq!(
+            policy.delay(20, &rate_limited("soon")),
+            Duration::from_secs(60)
+        );
+    }
+
+    #[test]
+    fn backoff_without_retry_after() {
+        let policy = RetryPolicy {
+            max: 3,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(60),
+        };
+        let resp = rate_limited("soon");
+
+        assert_eq!(policy.delay(0, &resp), Duration::from_millis(100));
+        assert_eq!(policy.delay(2, &resp), Duration::from_millis(400));
+    }
+
+    #[test]
+    fn connection_delay_jittered() {
+        let policy = RetryPolicy {
+            max: 3,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(60),
+        };
+
+        assert_eq!(
+            jitter(Duration::from_millis(400), 0.0),
+            Duration::from_millis(200)
+        );
+        assert_eq!(
+            jitter(Duration::from_millis(400), 0.5),
+            Duration::from_millis(300)
+        );
+
+        let delay = policy.connection_delay(2);
+        assert!(delay >= Duration::from_millis(200) && delay <= Duration::from_millis(400));
+    }
+}
@@ -0,0 +1,115 @@
+//! Secrets kept by the client for its whole lifetime.
+
+use hyper::header::HeaderValue;
+#[cfg(feature = "secure-memory")]
+use log::info;
+
+use crate::error::Result;
+
+/// Header value holding a secret, e.g. the encoded daemon credentials.
+/// With the `secure-memory` feature the buffer is locked in memory, so it
+/// can't be swapped to disk, and zeroed once dropped.
+///
+/// Each request gets its own copy of the header, marked sensitive: hyper
+/// moves requests to connection tasks which may outlive the client, so the
+/// buffer zeroed on drop is never shared with them. Those copies are neither
+/// locked nor zeroed.
+pub(crate) struct SecretHeader(Box<[u8]>);
+
+impl SecretHeader {
+    pub(crate) fn new(secret: String) -> Result<Self> {
+        if let Err(e) = HeaderValue::from_str(&secret) {
+            wipe(secret);
+            return Err(hyper::http::Error::from(e).into());
+        }
+
+        let mut buffer = vec![0; secret.len()].into_boxed_slice();
+        buffer.copy_from_slice(secret.as_bytes());
+        wipe(secret);
+
+        #[cfg(feature = "secure-memory")]
+        lock(&buffer);
+
+        Ok(Self(buffer))
+    }
+
+    /// Copy of the header to send with one request.
+    pub(crate) fn header(&self) -> HeaderValue {
+        let mut value = HeaderValue::from_bytes(&self.0).expect("checked in new");
+        value.set_sensitive(true);
+        value
+    }
+}
+
+impl Clone for SecretHeader {
+    /// Copy into a new buffer, locked like the original, so dropping
+    /// either one doesn't zero the other.
+    fn clone(&self) -> Self {
+        let buffer = self.0.clone();
+
+        #[cfg(feature = "secure-memory")]
+        lock(&buffer);
+
+        Self(buffer)
+    }
+}
+
+#[cfg(feature = "secure-memory")]
+impl Drop for SecretHeader {
+    /// Zero the buffer, owned by this header only, and unlock it. Best effort:
+    /// locks cover whole pages, so this also unlocks other secrets sharing
+    /// a page with it, e.g. the buffer of a clone.
+    fn drop(&mut self) {
+        unsafe {
+            memsec::memzero(self.0.as_mut_ptr(), self.0.len());
+            memsec::munlock(self.0.as_mut_ptr(), self.0.len());
+        }
+    }
+}
+
+/// Lock `secret` in memory. Best effort: the lock fails when over
+/// `RLIMIT_MEMLOCK`, then the secret may be swapped to disk.
+#[cfg(feature = "secure-memory")]
+fn lock(secret: &[u8]) {
+    let locked = unsafe { memsec::mlock(secret.as_ptr() as *mut u8, secret.len()) };
+
+    if !locked {
+        info!("Couldn't lock a secret in memory, it may be swapped to disk");
+    }
+}
+
+/// Zero the intermediate copy of a secret before freeing it.
+/// Without the `secure-memory` feature the string is simply dropped.
+pub(crate) fn wipe(mut secret: String) {
+    #[cfg(feature = "secure-memory")]
+    unsafe {
+        memsec::memzero(secret.as_mut_ptr(), secret.len());
+    }
+
+    secret.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_copies_secret_buffer() {
+        let header = SecretHeader::new("Basic dGVzdDp0ZXN0".to_string()).unwrap();
+
+        let value = header.header();
+        assert_eq!(value, "Basic dGVzdDp0ZXN0");
+        assert!(value.is_sensitive());
+        assert_ne!(value.as_bytes().as_ptr(), header.0.as_ptr());
+
+        let clone = header.clone();
+        assert_ne!(clone.0.as_ptr(), header.0.as_ptr());
+        drop(header);
+        assert_eq!(clone.header(), "Basic dGVzdDp0ZXN0");
+    }
+
+    #[test]
+    fn invalid_header_rejected() {
+        assert!(SecretHeader::new("Basic \n".to_string()).is_err());
+    }
+}
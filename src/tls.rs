@@ -0,0 +1,73 @@
+//! Certificate pinning for daemons with self-signed certificates.
+
+use std::time::SystemTime;
+
+use ring::digest::{digest, SHA256};
+use rustls::client::{ServerCertVerified, ServerCertVerifier};
+use rustls::{Certificate, CertificateError, Error, ServerName};
+
+/// Accepts only the server certificate with the given SHA-256 fingerprint,
+/// whoever issued it. Handshake signatures are still checked against it.
+pub(crate) struct PinnedCertVerifier {
+    fingerprint: [u8; 32],
+}
+
+impl PinnedCertVerifier {
+    pub(crate) fn new(fingerprint: [u8; 32]) -> Self {
+        Self { fingerprint }
+    }
+}
+
+impl ServerCertVerifier for PinnedCertVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, Error> {
+        if digest(&SHA256, &end_entity.0).as_ref() == self.fingerprint {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(Error::InvalidCertificate(
+                CertificateError::ApplicationVerificationFailure,
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryFrom;
+
+    fn verify(verifier: &PinnedCertVerifier, cert: &[u8]) -> Result<ServerCertVerified, Error> {
+        verifier.verify_server_cert(
+            &Certificate(cert.to_vec()),
+            &[],
+            &ServerName::try_from("electrum.local").unwrap(),
+            &mut std::iter::empty(),
+            &[],
+            SystemTime::now(),
+        )
+    }
+
+    #[test]
+    fn pinned_fingerprint() {
+        let cert = b"self-signed certificate of the daemon";
+        let mut fingerprint = [0; 32];
+        fingerprint.copy_from_slice(digest(&SHA256, cert).as_ref());
+
+        assert!(verify(&PinnedCertVerifier::new(fingerprint), cert).is_ok());
+
+        let res = verify(&PinnedCertVerifier::new([0; 32]), cert);
+        assert!(matches!(
+            res,
+            Err(Error::InvalidCertificate(
+                CertificateError::ApplicationVerificationFailure
+            ))
+        ));
+    }
+}
@@ -0,0 +1,201 @@
+//! Minimal parsing of raw bitcoin transactions.
+
+use crate::btc::OwnedBtcAddress;
+use crate::error::{ElectrumRpcError, Result};
+use crate::Electrum;
+
+/// Signed transaction created by the wallet but not broadcast yet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreparedTx {
+    hex: String,
+    base_size: usize,
+    total_size: usize,
+    destination: Option<OwnedBtcAddress>,
+}
+
+impl PreparedTx {
+    /// Parse raw transaction hex.
+    pub fn from_hex(hex: &str) -> Result<Self> {
+        let bytes = decode_hex(hex)?;
+        let base_size = RawTxReader::new(&bytes).base_size()?;
+
+        Ok(Self {
+            hex: hex.to_string(),
+            base_size,
+            total_size: bytes.len(),
+            destination: None,
+        })
+    }
+
+    /// Remember the address paid to, to tell it apart from change.
+    pub fn with_destination(mut self, destination: OwnedBtcAddress) -> Self {
+        self.destination = Some(destination);
+        self
+    }
+
+    /// Raw transaction hex.
+    pub fn hex(&self) -> &str {
+        &self.hex
+    }
+
+    /// Transaction weight in weight units (BIP 141).
+    pub fn weight(&self) -> usize {
+        self.base_size * 3 + self.total_size
+    }
+
+    /// Virtual size in vBytes, the size the fee rate is computed against.
+    pub fn vsize(&self) -> usize {
+        self.weight().div_ceil(4)
+    }
+
+    /// Find the change output: the output paying to the wallet
+    /// which isn't the destination of the payment.
+    /// Outputs are decoded and checked with `ismine` by the daemon.
+    pub async fn change_address(&self, client: &Electrum) -> Result<Option<OwnedBtcAddress>> {
+        for address in client
+            .output_addresses(&self.hex)
+            .await?
+            .into_iter()
+            .flatten()
+        {
+            if Some(&address) == self.destination.as_ref() {
+                continue;
+            }
+
+            if client.is_mine(&address.as_btc_address()).await? {
+                return Ok(Some(address));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+fn invalid(reason: &str) -> ElectrumRpcError {
+    ElectrumRpcError::InvalidTransaction(reason.to_string())
+}
+
+pub(crate) fn decode_hex(hex: &str) -> Result<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return Err(invalid("odd length of hex"));
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            hex.get(i..i + 2)
+                .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+                .ok_or_else(|| invalid("not a hex string"))
+        })
+        .collect()
+}
+
+struct RawTxReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> RawTxReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, pos: 0 }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| invalid("unexpected end of transaction"))?;
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+
+        Ok(slice)
+    }
+
+    fn var_int(&mut self) -> Result<usize> {
+        let len = match self.take(1)?[0] {
+            0xfd => 2,
+            0xfe => 4,
+            0xff => 8,
+            value => return Ok(value as usize),
+        };
+
+        let value = self
+            .take(len)?
+            .iter()
+            .rev()
+            .fold(0u64, |acc, byte| acc << 8 | *byte as u64);
+
+        Ok(value as usize)
+    }
+
+    fn skip_var_bytes(&mut self) -> Result<()> {
+        let len = self.var_int()?;
+        self.take(len)?;
+        Ok(())
+    }
+
+    /// Size of the transaction serialized without witness data.
+    fn base_size(mut self) -> Result<usize> {
+        self.take(4)?; // version
+
+        let segwit = self.bytes.get(4..6) == Some(&[0x00, 0x01][..]);
+        if segwit {
+            self.take(2)?;
+        }
+
+        let inputs = self.var_int()?;
+        for _ in 0..inputs {
+            self.take(36)?; // prevout
+            self.skip_var_bytes()?; // script_sig
+            self.take(4)?; // sequence
+        }
+
+        let outputs = self.var_int()?;
+        for _ in 0..outputs {
+            self.take(8)?; // value
+            self.skip_var_bytes()?; // script_pubkey
+        }
+
+        let witness_start = self.pos;
+        if segwit {
+            for _ in 0..inputs {
+                let items = self.var_int()?;
+                for _ in 0..items {
+                    self.skip_var_bytes()?;
+                }
+            }
+        }
+        let witness_size = self.pos - witness_start;
+
+        self.take(4)?; // locktime
+        if self.pos != self.bytes.len() {
+            return Err(invalid("trailing bytes after locktime"));
+        }
+
+        let marker_size = if segwit { 2 } else { 0 };
+        Ok(self.bytes.len() - witness_size - marker_size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEGWIT_TX: &str = "02000000000101b58c5be9c9ce77a8bacd01779fdcfbf566a936a5b89482d1bc3114525ee5f3ea0000000000fdffffff02e8030000000000001600149e08b7dbcd1aad00ec6888a60e56c779f0f2da276022000000000000160014d272035ef819d6311231c06014aed5cfb100009e0247304402203db69d69b3fa76050b6c3276bc21bb834996f2c84c31c17c813beba01079705002202d864669f12db9939ea78a45e4c4a982cca68304fef25f334bd6cbbc9971bc9b012103815054ce939185772574ef569fe31b601d5bad48f48d5edaef194cded838c31ac40f1e00";
+
+    #[test]
+    fn segwit_tx_size() {
+        let tx = PreparedTx::from_hex(SEGWIT_TX).unwrap();
+
+        assert_eq!(tx.weight(), 561);
+        assert_eq!(tx.vsize(), 141);
+    }
+
+    #[test]
+    fn truncated_tx() {
+        let res = PreparedTx::from_hex(&SEGWIT_TX[..100]);
+
+        assert!(matches!(res, Err(ElectrumRpcError::InvalidTransaction(_))));
+    }
+}
@@ -0,0 +1,148 @@
+//! Built-in http server receiving Electrum's `notify` POST requests.
+
+use std::convert::Infallible;
+use std::net::SocketAddr;
+
+use hyper::body;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode, Uri};
+use serde::{Deserialize, Deserializer};
+use tokio::sync::{mpsc, oneshot};
+
+use crate::btc::OwnedBtcAddress;
+use crate::error::Result;
+
+/// Notification posted by the daemon when the status of a watched address changes.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct NotifyEvent {
+    pub address: OwnedBtcAddress,
+    /// Electrum status hash of the address history, empty if it has no history.
+    #[serde(deserialize_with = "null_as_empty")]
+    pub status: String,
+}
+
+fn null_as_empty<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<String, D::Error> {
+    Ok(Option::<String>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+/// Server collecting notifications posted by the daemon.
+/// The server is shut down when dropped.
+pub struct WebhookServer {
+    url: Uri,
+    events: mpsc::UnboundedReceiver<NotifyEvent>,
+    _shutdown: oneshot::Sender<()>,
+}
+
+impl WebhookServer {
+    /// Start listening on `addr`. Must be called within tokio runtime.
+    /// The daemon has to be able to reach the bound address.
+    pub fn bind(addr: SocketAddr) -> Result<Self> {
+        let (sender, events) = mpsc::unbounded_channel();
+        let (shutdown, stopped) = oneshot::channel::<()>();
+
+        let make_service = make_service_fn(move |_| {
+            let sender = sender.clone();
+
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req: Request<Body>| {
+                    let sender = sender.clone();
+
+                    async move {
+                        if req.method() != Method::POST {
+                            let mut resp = Response::new(Body::empty());
+                            *resp.status_mut() = StatusCode::METHOD_NOT_ALLOWED;
+                            return Ok::<_, hyper::Error>(resp);
+                        }
+
+                        let payload = body::to_bytes(req.into_body()).await?;
+                        let event = match serde_json::from_slice::<NotifyEvent>(&payload) {
+                            Ok(event) => event,
+                            Err(_) => {
+                                let mut resp = Response::new(Body::empty());
+                                *resp.status_mut() = StatusCode::BAD_REQUEST;
+                                return Ok(resp);
+                            }
+                        };
+                        let _ = sender.send(event);
+
+                        Ok(Response::new(Body::empty()))
+                    }
+                }))
+            }
+        });
+
+        let server = Server::try_bind(&addr)?.serve(make_service);
+        let url = format!("http://{}/notify", server.local_addr()).parse::<Uri>()?;
+        tokio::spawn(server.with_graceful_shutdown(async {
+            let _ = stopped.await;
+        }));
+
+        Ok(Self {
+            url,
+            events,
+            _shutdown: shutdown,
+        })
+    }
+
+    /// Url to register with `Electrum::notify`.
+    pub fn url(&self) -> &Uri {
+        &self.url
+    }
+
+    /// Wait for the next notification. Malformed posts are answered
+    /// with `400 Bad Request` and never show up here.
+    pub async fn next_event(&mut self) -> Option<NotifyEvent> {
+        self.events.recv().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::Client;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn notify_event_parsed() {
+        let mut webhook = WebhookServer::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let client = Client::new();
+
+        let body = r#"{"address": "tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn", "status": "e1d2b4a8c3f0b1a7e9cf1ed0a8f6c0b6c8a2a93f4e3dbf21c6c6f1f40b9e8d7a"}"#;
+        let req = Request::post(webhook.url().clone())
+            .body(Body::from(body))
+            .unwrap();
+        let resp = client.request(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let event = webhook.next_event().await.unwrap();
+        assert_eq!(
+            event.address.address,
+            "tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn"
+        );
+        assert_eq!(
+            event.status,
+            "e1d2b4a8c3f0b1a7e9cf1ed0a8f6c0b6c8a2a93f4e3dbf21c6c6f1f40b9e8d7a"
+        );
+
+        let body = r#"{"address": "tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn", "status": null}"#;
+        let req = Request::post(webhook.url().clone())
+            .body(Body::from(body))
+            .unwrap();
+        client.request(req).await.unwrap();
+        assert_eq!(webhook.next_event().await.unwrap().status, "");
+    }
+
+    #[tokio::test]
+    async fn notify_malformed_body_rejected() {
+        let webhook = WebhookServer::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+
+        let req = Request::post(webhook.url().clone())
+            .body(Body::from("{}"))
+            .unwrap();
+        let resp = Client::new().request(req).await.unwrap();
+
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -3,13 +3,13 @@
 use std::path::PathBuf;
 
 use hyper::{body, Uri};
-use rust_decimal::Decimal;
 use rust_decimal::prelude::FromPrimitive;
+use rust_decimal::Decimal;
 use serde_json::Value;
-use tokio;
 
 use electrum_jsonrpc::btc::BtcAddress;
 use electrum_jsonrpc::ext::tests::*;
+use electrum_jsonrpc::PayToOptions;
 
 #[tokio::test]
 async fn call_method_help() {
@@ -22,7 +22,7 @@ async fn call_method_help() {
 async fn call_method_get_info() {
     let electrum = get_electrum_rpc();
 
-    let res = electrum.get_info().await.unwrap();
+    let res = electrum.get_info_raw().await.unwrap();
     assert_eq!(res.status(), 200);
 }
 
@@ -112,7 +112,7 @@ async fn call_method_restore_wallet() {
 
     let seed_phrase =
         "clever city snake tonight action output garbage gun upset raven pudding know";
-    let res = electrum.restore_wallet(&seed_phrase).await.unwrap();
+    let res = electrum.restore_wallet(seed_phrase).await.unwrap();
     let slice = body::to_bytes(res).await.unwrap();
 
     let json: Value = serde_json::from_slice(&slice).unwrap();
@@ -134,7 +134,10 @@ async fn call_method_pay_to() {
     let electrum = get_electrum_rpc();
     let addr = BtcAddress::new("tb1qncyt0k7dr2kspmrg3znqu4k808c09k385v38dn");
     let amount = Decimal::from_f64(0.00001).unwrap();
-    let res = electrum.pay_to(&addr, amount, None).await.unwrap();
+    let res = electrum
+        .pay_to(&addr, amount, &PayToOptions::new())
+        .await
+        .unwrap();
     let slice = body::to_bytes(res).await.unwrap();
 
     let json: Value = serde_json::from_slice(&slice).unwrap();
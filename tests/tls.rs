@@ -0,0 +1,18 @@
+//! Integration tests for Electrum daemons behind TLS.
+//! Set `ELECTRUM_HTTPS_ADDRESS` to the `https://` address of the daemon.
+#![cfg(feature = "tls")]
+
+use std::env;
+
+use electrum_jsonrpc::ext::tests::*;
+use electrum_jsonrpc::Electrum;
+
+#[tokio::test]
+async fn call_method_help_over_https() {
+    let address =
+        env::var("ELECTRUM_HTTPS_ADDRESS").unwrap_or_else(|_| "https://127.0.0.1:7443".to_string());
+    let electrum = Electrum::new(LOGIN.clone(), PASSWORD.clone(), address).unwrap();
+
+    let res = electrum.get_help().await.unwrap();
+    assert_eq!(res.status(), 200);
+}